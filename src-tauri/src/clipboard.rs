@@ -0,0 +1,96 @@
+use super::get_app_handle;
+
+use tauri::ClipboardManager;
+
+use std::sync::{ Mutex, MutexGuard };
+use once_cell::sync::Lazy;
+
+// The system clipboard is only available once the app has started, so its backend is replaced during setup
+static CLIPBOARD: Lazy<Mutex<Clipboard>> = Lazy::new(|| Mutex::new(Clipboard::new(Box::new(MemoryClipboard::default()))));
+
+// Where clipboard text is read from and written to. This is a trait so the system clipboard can be swapped out,
+// for example for the in-memory one.
+pub trait ClipboardBackend: Send {
+    fn get_text(&mut self) -> Option<String>;
+    fn set_text(&mut self, text: String) -> Result<(), String>;
+}
+
+// The system clipboard, through Tauri
+pub struct TauriClipboard;
+
+impl ClipboardBackend for TauriClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        get_app_handle()?.clipboard_manager().read_text().ok().flatten()
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        match get_app_handle() {
+            Some(app_handle) => app_handle.clipboard_manager().write_text(text).map_err(|error| error.to_string()),
+            None => Err("The app hasn't started yet".to_string())
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        self.text = Some(text);
+        Ok(())
+    }
+}
+
+// The clipboard macros use, which remembers what they put on it
+pub struct Clipboard {
+    backend: Box<dyn ClipboardBackend>,
+    // The last text a macro put on the clipboard, so the clipboard initiator doesn't run for its own changes
+    last_set_text: Option<String>,
+}
+
+impl Clipboard {
+    pub fn new(backend: Box<dyn ClipboardBackend>) -> Clipboard {
+        Clipboard { backend, last_set_text: None }
+    }
+
+    pub fn set_backend(&mut self, backend: Box<dyn ClipboardBackend>) {
+        self.backend = backend;
+    }
+
+    pub fn get_text(&mut self) -> Option<String> {
+        self.backend.get_text()
+    }
+
+    pub fn set_text(&mut self, text: String) -> Result<(), String> {
+        self.backend.set_text(text.clone())?;
+        self.last_set_text = Some(text);
+        Ok(())
+    }
+
+    // Only the first change after a macro sets the clipboard is the macro's own, so later copies of the same text still count
+    pub fn take_last_set_text(&mut self) -> Option<String> {
+        self.last_set_text.take()
+    }
+}
+
+pub fn lock_clipboard() -> MutexGuard<'static, Clipboard> {
+    CLIPBOARD.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+pub fn set_clipboard_backend(backend: Box<dyn ClipboardBackend>) {
+    lock_clipboard().set_backend(backend);
+}
+
+pub fn get_clipboard_text() -> Option<String> {
+    lock_clipboard().get_text()
+}
+
+pub fn set_clipboard_text(text: String) -> Result<(), String> {
+    lock_clipboard().set_text(text)
+}
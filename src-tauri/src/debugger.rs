@@ -0,0 +1,135 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::sync::mpsc::{ channel, Receiver, RecvTimeoutError, Sender };
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tauri::Manager;
+
+use super::get_app_handle;
+use super::execute::{ spawn_macro_run, get_execution_type, Execution, Variables, VariableValue, MacroResult };
+use super::initiators::Initiator;
+use super::running::{ start_run, stop_run, is_stop_requested, MacroRun };
+use super::Macro;
+
+// The frontend talks to a debug session by its run ID
+static DEBUG_SESSIONS: Lazy<Mutex<HashMap<u64, DebugHandle>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugCommand {
+    Step,
+    Continue,
+    Stop,
+}
+
+struct DebugHandle {
+    sender: Sender<DebugCommand>,
+    breakpoints: Arc<Mutex<Vec<String>>>,
+}
+
+// Lives in the macro run, and pauses it before executions until the frontend says to go on
+pub struct Debugger {
+    receiver: Receiver<DebugCommand>,
+    breakpoints: Arc<Mutex<Vec<String>>>,
+    // Whether to pause before the next execution, even if it has no breakpoint
+    stepping: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DebugPaused {
+    id: u64,
+    path: String,
+    execution: String,
+    variables: HashMap<String, VariableValue>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DebugFinished {
+    id: u64,
+    success: bool,
+}
+
+impl Debugger {
+    // Returns false if the run should stop
+    pub fn before_execution(&mut self, id: u64, path: &str, execution: &Execution, variables: &Variables) -> bool {
+        if !self.stepping && !self.breakpoints.lock().unwrap().iter().any(|breakpoint| breakpoint == path) {
+            return true;
+        }
+
+        if let Some(app_handle) = get_app_handle() {
+            let _ = app_handle.emit_all("debug-paused", DebugPaused {
+                id,
+                path: path.to_string(),
+                execution: get_execution_type(execution).to_string(),
+                variables: variables.iter().map(|(name, variable)| (name.clone(), variable.value.clone())).collect(),
+            });
+        }
+
+        // Also checks for the run being stopped some other way, like from the tray
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(50)) {
+                Ok(DebugCommand::Step) => {
+                    self.stepping = true;
+                    return true;
+                }
+                Ok(DebugCommand::Continue) => {
+                    self.stepping = false;
+                    return true;
+                }
+                Ok(DebugCommand::Stop) => return false,
+                Err(RecvTimeoutError::Timeout) if !is_stop_requested(id) => {}
+                Err(_) => return false
+            }
+        }
+    }
+}
+
+// Starts a macro run that pauses at breakpoints, which are node paths like "initiator:0/2/then/1" or "function:My macro:0/1".
+// Without any breakpoints, it pauses before the first execution.
+pub fn start_debug_run(initiator: Initiator, initiator_index: usize, macro_: Macro, variables: Variables, breakpoints: Vec<String>) -> u64 {
+    println!("Debugging macro initiator from macro \"{}\"", macro_.name);
+    let mut run: MacroRun = start_run(&macro_.name, "debug");
+    let id: u64 = run.id;
+    let (sender, receiver) = channel::<DebugCommand>();
+    let breakpoints: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(breakpoints));
+
+    run.path = vec![format!("initiator:{}", initiator_index)];
+    run.debugger = Some(Debugger {
+        receiver,
+        stepping: breakpoints.lock().unwrap().is_empty(),
+        breakpoints: breakpoints.clone(),
+    });
+    DEBUG_SESSIONS.lock().unwrap().insert(id, DebugHandle { sender, breakpoints });
+
+    let result_receiver: Receiver<MacroResult> = spawn_macro_run(initiator, macro_, variables, run);
+    thread::spawn(move || {
+        let success: bool = result_receiver.recv().map(|result| result.success).unwrap_or(false);
+        DEBUG_SESSIONS.lock().unwrap().remove(&id);
+        if let Some(app_handle) = get_app_handle() {
+            let _ = app_handle.emit_all("debug-finished", DebugFinished { id, success });
+        }
+    });
+
+    id
+}
+
+pub fn send_debug_command(id: u64, command: DebugCommand) -> Result<(), String> {
+    if command == DebugCommand::Stop {
+        stop_run(id);
+    }
+    let sessions = DEBUG_SESSIONS.lock().unwrap();
+    let session: &DebugHandle = sessions.get(&id).ok_or(format!("Run {} isn't being debugged", id))?;
+    session.sender.send(command).map_err(|_| format!("Run {} has already finished", id))
+}
+
+pub fn set_debug_breakpoints(id: u64, breakpoints: Vec<String>) -> Result<(), String> {
+    let sessions = DEBUG_SESSIONS.lock().unwrap();
+    let session: &DebugHandle = sessions.get(&id).ok_or(format!("Run {} isn't being debugged", id))?;
+    *session.breakpoints.lock().unwrap() = breakpoints;
+    Ok(())
+}
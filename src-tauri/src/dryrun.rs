@@ -0,0 +1,150 @@
+use serde::Serialize;
+
+use super::execute::{ Execution, Variables, parse_string, get_execution_type, evaluate_number, get_click_count };
+use super::input::InputEvent;
+
+// Something a macro would have done if it wasn't a dry run
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunAction {
+    // The type of the execution, like "presskey"
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRun {
+    // False if the code had an error or was stopped
+    pub success: bool,
+    pub actions: Vec<DryRunAction>,
+    // The exact keyboard and mouse events the input executions would have sent
+    pub inputs: Vec<InputEvent>,
+}
+
+// Executions that change something outside of the macro are recorded in a dry run instead of being done.
+// Everything else, like reading files and setting variables, still runs so the rest of the code behaves the same.
+pub fn get_dry_run_action(execution: &Execution, variables: &mut Variables) -> Option<DryRunAction> {
+    let description: String = match execution {
+        Execution::Wait { data } => format!("Wait {} seconds", data.time),
+        Execution::Notification { data } => format!(
+            "Show the notification \"{}\": \"{}\"",
+            parse_string(&data.title, variables),
+            parse_string(&data.message, variables)
+        ),
+        Execution::TypeString { data } => format!("Type \"{}\"", parse_string(&data.string, variables)),
+        Execution::MouseMoveRelative { data } => format!(
+            "Move the mouse by {}, {}",
+            evaluate_number(&data.x, variables).round(),
+            evaluate_number(&data.y, variables).round()
+        ),
+        Execution::MouseMoveAbsolute { data } => format!(
+            "Move the mouse to {}, {}",
+            evaluate_number(&data.x, variables).round(),
+            evaluate_number(&data.y, variables).round()
+        ),
+        Execution::PressKey { data } => format!("Press {}", data.key),
+        Execution::ReleaseKey { data } => format!("Release {}", data.key),
+        Execution::SendHotkey { data } => format!("Press {}", parse_string(&data.keys, variables)),
+        Execution::PressMouse { data } => format!("Press {}", data.button),
+        Execution::ReleaseMouse { data } => format!("Release {}", data.button),
+        Execution::Click { data } => format!("Click {} {} times", data.button, get_click_count(data)),
+        Execution::ScrollVertical { data } => format!("Scroll vertically by {}", data.amount.round()),
+        Execution::ScrollHorizontal { data } => format!("Scroll horizontally by {}", data.amount.round()),
+        Execution::Drag { data } => format!(
+            "Drag {} from {}, {} to {}, {}",
+            data.button,
+            evaluate_number(&data.fromx, variables).round(),
+            evaluate_number(&data.fromy, variables).round(),
+            evaluate_number(&data.tox, variables).round(),
+            evaluate_number(&data.toy, variables).round()
+        ),
+        Execution::WriteFile { data } => format!("Write \"{}\" to {}", parse_string(&data.content, variables), data.file),
+        Execution::DeleteFile { data } => format!("Delete the file {}", data.file),
+        Execution::CreateFolder { data } => format!("Create the folder {}", data.path),
+        Execution::DeleteFolder { data } => format!("Delete the folder {}", data.path),
+        Execution::Log { data } => format!("Log \"{}\"", parse_string(&data.message, variables)),
+        Execution::ClearLog { } => "Clear the log".to_string(),
+        Execution::SetClipboard { data } => format!("Set the clipboard to \"{}\"", parse_string(&data.content, variables)),
+        Execution::RunMacro { data } => format!("Run initiator {} of macro \"{}\"", data.initiator.max(0.0) as usize, data.macro_),
+        _ => return None
+    };
+
+    Some(DryRunAction { type_: get_execution_type(execution).to_string(), description })
+}
+
+// Executions that only send keyboard or mouse input, which a dry run records from its input backend
+pub fn is_input_execution(execution: &Execution) -> bool {
+    matches!(execution,
+        Execution::TypeString { .. } | Execution::MouseMoveRelative { .. } | Execution::MouseMoveAbsolute { .. } |
+        Execution::PressKey { .. } | Execution::ReleaseKey { .. } | Execution::SendHotkey { .. } |
+        Execution::PressMouse { .. } | Execution::ReleaseMouse { .. } | Execution::Click { .. } |
+        Execution::ScrollVertical { .. } | Execution::ScrollHorizontal { .. } | Execution::Drag { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Macro;
+    use super::super::initiators::Initiator;
+    use super::super::execute::{ dry_run_macro, get_json_variables };
+    use super::super::paths::use_test_data_dir;
+
+    use serde_json::json;
+
+    fn get_action(execution: serde_json::Value) -> Option<(String, String)> {
+        let execution: Execution = serde_json::from_value(execution).unwrap();
+        let mut variables: Variables = get_json_variables(&json!({ "name": "World", "x": 4 })).unwrap();
+        get_dry_run_action(&execution, &mut variables).map(|action| (action.type_, action.description))
+    }
+
+    #[test]
+    fn describes_actions_with_variables() {
+        assert_eq!(
+            get_action(json!({ "type_": "notification", "data": { "title": "Hi", "message": "Hello {{name}}" } })),
+            Some(("notification".to_string(), "Show the notification \"Hi\": \"Hello World\"".to_string()))
+        );
+        assert_eq!(
+            get_action(json!({ "type_": "movemouseabsolute", "data": { "x": { "type_": "variable", "variable": "x" }, "y": 2.6 } })),
+            Some(("movemouseabsolute".to_string(), "Move the mouse to 4, 3".to_string()))
+        );
+        assert_eq!(
+            get_action(json!({ "type_": "runmacro", "data": { "macro_": "Other", "initiator": -1, "inputs": "", "wait": "wait", "output": "" } })),
+            Some(("runmacro".to_string(), "Run initiator 0 of macro \"Other\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn runs_everything_else() {
+        assert_eq!(get_action(json!({ "type_": "setvariable", "data": { "variable": "y", "content": { "type_": "number", "value": 1 } } })), None);
+        assert_eq!(get_action(json!({ "type_": "stop" })), None);
+    }
+
+    #[test]
+    fn records_instead_of_doing() {
+        use_test_data_dir();
+        let file: std::path::PathBuf = std::env::temp_dir().join(format!("codemacros-dry-run-{}.txt", std::process::id()));
+        let initiator: Initiator = serde_json::from_value(json!({ "type_": "manual", "data": {}, "executes": [
+            { "type_": "writefile", "data": { "file": file.to_string_lossy(), "content": "Hello {{name}}" } },
+            { "type_": "presskey", "data": { "key": "a" } }
+        ], "variables": null })).unwrap();
+        let macro_: Macro = serde_json::from_value(json!({
+            "name": "Dry run test", "description": "", "macro_": { "initiators": [], "functions": null }
+        })).unwrap();
+
+        let (_, dry_run) = dry_run_macro(initiator, macro_, get_json_variables(&json!({ "name": "World" })).unwrap());
+        assert!(dry_run.success);
+        assert_eq!(
+            dry_run.actions.iter().map(|action| action.description.clone()).collect::<Vec<String>>(),
+            vec![format!("Write \"Hello World\" to {}", file.display()), "Press a".to_string()]
+        );
+        // The key is released when the run ends, like a real run would
+        assert_eq!(dry_run.inputs, vec![
+            InputEvent::PressKey { key: "a".to_string() },
+            InputEvent::ReleaseKey { key: "a".to_string() },
+        ]);
+        assert!(!file.exists());
+    }
+}
@@ -1,22 +1,65 @@
 use serde::{ Deserialize, Serialize };
 
-use std::{collections::HashMap, path::Path};
+use std::collections::HashMap;
 
-use tauri::{api::notification::Notification, PathResolver, Manager, Window};
+use tauri::api::notification::Notification;
 
 static MAX_LOOP_ITERATIONS: u64 = 100000;
+pub static MAX_CHAIN_DEPTH: u32 = 16;
+
+// Anything past a triple click is most likely a mistake, like a variable in the wrong field
+static MAX_CLICKS: u32 = 10;
+
+// In milliseconds
+static CLICK_INTERVAL: u64 = 50;
+static DRAG_STEP_INTERVAL: u64 = 10;
+static MIN_DRAG_STEP_INTERVAL: u64 = 5;
+static MIN_DRAG_STEPS: u32 = 10;
 
 use std::thread;
 
+use std::panic::{ self, AssertUnwindSafe };
+
+use std::sync::mpsc::{ channel, Receiver, RecvTimeoutError };
+
+use std::sync::{ Arc, Mutex };
+
 use super::Macro;
 
 use super::initiators::Initiator;
 
-use super::get_app_handle;
+use super::get_macros;
+
+use super::is_paused;
+
+use super::running::{ start_run, stop_run, get_timestamp, MacroRun };
+
+use super::clipboard::{ get_clipboard_text, set_clipboard_text };
+
+use super::initiators::chain::run_initiator_macro_completed;
+
+use super::functions::resolve_function;
+
+use super::dryrun::{ get_dry_run_action, is_input_execution, DryRun, DryRunAction };
+use super::input::{ InputBackend, InputEvent, RecordingBackend };
+
+use super::history::{ record_run, RunRecord, TraceEntry, MAX_TRACE_ENTRIES };
+
+use super::settings::get_settings;
+
+use super::log::{ write_log_entry, clear_log };
+
+use super::keys::{ needs_shift, parse_hotkey, tap_keys, get_named_button };
 
-use tauri::AppHandle;
+use super::typing::type_string;
 
-use inputbot::{KeySequence, MouseCursor, KeybdKey, MouseButton, get_keybd_key};
+use super::screen::{ get_screen_bounds, ScreenBounds };
+
+use std::time::Instant;
+
+use std::any::Any;
+
+use inputbot::{KeybdKey, MouseButton, get_keybd_key};
 
 use std::fs;
 
@@ -103,12 +146,33 @@ pub enum Execution {
     ReleaseKey {
         data: ReleaseKeyData
     },
+    SendHotkey {
+        data: SendHotkeyData
+    },
     PressMouse {
         data: PressMouseData
     },
     ReleaseMouse {
         data: ReleaseMouseData
     },
+    Click {
+        data: ClickData
+    },
+    ScrollVertical {
+        data: ScrollData
+    },
+    ScrollHorizontal {
+        data: ScrollData
+    },
+    Drag {
+        data: DragData
+    },
+    GetMousePosition {
+        data: GetMousePositionData
+    },
+    GetScreenSize {
+        data: GetScreenSizeData
+    },
     ReadFile {
         data: ReadFileData
     },
@@ -169,6 +233,15 @@ pub enum Execution {
     SortArray {
         data: SortArrayData
     },
+    GetClipboard {
+        data: GetClipboardData
+    },
+    RunMacro {
+        data: RunMacroData
+    },
+    SetClipboard {
+        data: SetClipboardData
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -212,19 +285,25 @@ pub struct SetVariableData {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TypeStringData {
-    pub string: String
+    pub string: String,
+    // Milliseconds to wait between each character
+    #[serde(default)]
+    pub delay: f64,
+    // "keys" presses the keys named in braces, like {enter}. Anything else types braces as they are.
+    #[serde(default)]
+    pub braces: String
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MouseMoveRelativeData {
-    pub x: f64,
-    pub y: f64
+    pub x: NumberExpression,
+    pub y: NumberExpression
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MouseMoveAbsoluteData {
-    pub x: f64,
-    pub y: f64
+    pub x: NumberExpression,
+    pub y: NumberExpression
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -237,6 +316,12 @@ pub struct ReleaseKeyData {
     pub key: String
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SendHotkeyData {
+    // Key names joined with "+", like "ctrl+shift+t"
+    pub keys: String
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PressMouseData {
     pub button: String
@@ -247,6 +332,52 @@ pub struct ReleaseMouseData {
     pub button: String
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClickData {
+    pub button: String,
+    // 2 for a double click, 3 for a triple click
+    pub count: f64
+}
+
+pub fn get_click_count(data: &ClickData) -> u32 {
+    (data.count.max(1.0) as u32).min(MAX_CLICKS)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScrollData {
+    // In notches of the scroll wheel. Positive scrolls up or right.
+    pub amount: f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DragData {
+    pub button: String,
+    pub fromx: NumberExpression,
+    pub fromy: NumberExpression,
+    pub tox: NumberExpression,
+    pub toy: NumberExpression,
+    // How long moving from one point to the other takes, in seconds
+    pub duration: f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetMousePositionData {
+    // The variables to set
+    pub x: String,
+    pub y: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetScreenSizeData {
+    // "primary" or "mouse", for the monitor the mouse is on
+    pub monitor: String,
+    // The variables to set. Any that are empty aren't set.
+    pub width: String,
+    pub height: String,
+    pub x: String,
+    pub y: String
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ReadFileData {
     pub file: String,
@@ -330,7 +461,13 @@ pub struct GetFolderContentsData {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LogData {
-    pub message: String
+    pub message: String,
+    #[serde(default = "default_log_level")]
+    pub level: String
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -359,6 +496,26 @@ pub struct SortArrayData {
     pub output: String
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetClipboardData {
+    pub output: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetClipboardData {
+    pub content: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunMacroData {
+    pub macro_: String,
+    pub initiator: f64,
+    // Names of variables to copy into the other macro, separated by commas
+    pub inputs: String,
+    pub wait: String,
+    pub output: String
+}
+
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct VariableType {
@@ -387,6 +544,14 @@ pub enum Condition {
 }
 
 
+// Numbers that can be worked out from variables. Plain numbers are still accepted, since that's how they used to be saved.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum NumberExpression {
+    Number(f64),
+    Expression(Expression)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "type_")]
 #[serde(rename_all = "lowercase")]
@@ -431,6 +596,8 @@ pub struct Function {
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub executes: Vec<Execution>,
+    #[serde(default)]
+    pub shared: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -440,21 +607,232 @@ pub struct Parameter {
     pub default_value: String,
 }
 
-pub fn run_macro_initiator(initiator: Initiator, macro_: Macro) {
-    println!("Running macro initiator from macro \"{}\"", macro_.name);
+pub fn run_macro_initiator(initiator: Initiator, initiator_index: usize, macro_: Macro) {
+    run_macro_initiator_with_variables(initiator, initiator_index, macro_, Variables::new());
+}
+
+pub fn run_macro_initiator_with_variables(initiator: Initiator, initiator_index: usize, macro_: Macro, variables: Variables) {
+    if !is_initiator_active(&initiator, &macro_) {
+        return;
+    }
+    let trigger: String = initiator.type_.clone();
+    start_macro_run(initiator, initiator_index, macro_, variables, trigger);
+}
+
+// Initiators don't run while macros are paused, or while they or their macro are disabled
+pub fn is_initiator_active(initiator: &Initiator, macro_: &Macro) -> bool {
+    !is_paused() && macro_.enabled && initiator.enabled
+}
+
+// Runs the initiator's code on a new thread and returns the ID it has in the running macro registry
+pub fn start_macro_run(initiator: Initiator, initiator_index: usize, macro_: Macro, variables: Variables, trigger: String) -> u64 {
+    start_chained_macro_run(initiator, initiator_index, macro_, variables, trigger, 0).0
+}
+
+pub struct MacroResult {
+    pub success: bool,
+    pub variables: Variables,
+}
+
+// Like start_macro_run, but for runs started by other macros. The receiver gets the result once the run finishes.
+pub fn start_chained_macro_run(initiator: Initiator, initiator_index: usize, macro_: Macro, variables: Variables, trigger: String, depth: u32) -> (u64, Receiver<MacroResult>) {
+    println!("Running macro initiator from macro \"{}\" ({})", macro_.name, trigger);
+    let mut run: MacroRun = start_run(&macro_.name, &trigger);
+    run.depth = depth;
+    run.path = vec![format!("initiator:{}", initiator_index)];
+    let run_id: u64 = run.id;
+    (run_id, spawn_macro_run(initiator, macro_, variables, run))
+}
+
+// Runs the initiator's code on a new thread with a run that's already been set up, including the path of the initiator
+pub fn spawn_macro_run(initiator: Initiator, macro_: Macro, variables: Variables, mut run: MacroRun) -> Receiver<MacroResult> {
+    let depth: u32 = run.depth;
+    if get_settings().trace_runs {
+        run.trace = Some(Vec::new());
+    }
+    let (sender, receiver) = channel::<MacroResult>();
     thread::spawn(move || {
-        let mut new_variables: Variables = Variables::new();
-        execute_macro_code(&initiator.executes, &mut new_variables, &mut false, macro_);
+        let mut new_variables: Variables = variables;
+        // Blocks like deleting a file that doesn't exist panic, which counts as the run failing
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            execute_macro_code(&initiator.executes, &mut new_variables, &mut run, macro_.clone());
+        }));
+        let stopped: bool = run.stop_requested();
+        let success: bool = result.is_ok() && !stopped;
+        let error: Option<String> = result.err().map(|error| get_panic_message(&error));
+        if let Some(error) = &error {
+            write_log_entry(&macro_.name, "error", error);
+        }
+
+        record_run(RunRecord {
+            id: run.id,
+            macro_name: run.macro_name.clone(),
+            trigger: run.trigger.clone(),
+            started: run.started,
+            ended: get_timestamp(),
+            outcome: (if stopped { "stopped" } else if success { "succeeded" } else { "failed" }).to_string(),
+            error,
+            trace: run.trace.take(),
+        });
+        drop(run);
+
+        run_initiator_macro_completed(&macro_.name, success, depth);
+        let _ = sender.send(MacroResult { success, variables: new_variables });
     });
+    receiver
+}
+
+fn get_panic_message(error: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = error.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = error.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "The macro had an unknown error".to_string()
+    }
+}
+
+// Runs the initiator's code on the current thread without doing anything outside of the macro, and returns what it would have done
+pub fn dry_run_macro(initiator: Initiator, macro_: Macro, variables: Variables) -> (u64, DryRun) {
+    println!("Dry running macro initiator from macro \"{}\"", macro_.name);
+    let mut run: MacroRun = start_run(&macro_.name, "dry run");
+    run.dry_run = Some(Vec::new());
+    let recorder: RecordingBackend = RecordingBackend::default();
+    let events: Arc<Mutex<Vec<InputEvent>>> = recorder.get_events();
+    run.set_input_backend(Box::new(recorder));
+
+    let mut new_variables: Variables = variables;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        execute_macro_code(&initiator.executes, &mut new_variables, &mut run, macro_.clone());
+    }));
+
+    let id: u64 = run.id;
+    let success: bool = result.is_ok() && !run.stop_requested();
+    let actions: Vec<DryRunAction> = run.dry_run.take().unwrap_or_default();
+    // Dropping the run releases anything it left held, which should be in the events too
+    drop(run);
+    let inputs: Vec<InputEvent> = events.lock().unwrap().clone();
+
+    (id, DryRun { success, actions, inputs })
+}
+
+// The type the execution has in the macro config, like "presskey"
+pub fn get_execution_type(execution: &Execution) -> &'static str {
+    match execution {
+        Execution::If { .. } => "if",
+        Execution::Function { .. } => "function",
+        Execution::FromToLoop { .. } => "fromtoloop",
+        Execution::WhileLoop { .. } => "whileloop",
+        Execution::Notification { .. } => "notification",
+        Execution::Wait { .. } => "wait",
+        Execution::SetVariable { .. } => "setvariable",
+        Execution::TypeString { .. } => "typestring",
+        Execution::Stop { .. } => "stop",
+        Execution::MouseMoveRelative { .. } => "movemouserelative",
+        Execution::MouseMoveAbsolute { .. } => "movemouseabsolute",
+        Execution::PressKey { .. } => "presskey",
+        Execution::ReleaseKey { .. } => "releasekey",
+        Execution::SendHotkey { .. } => "sendhotkey",
+        Execution::PressMouse { .. } => "pressmouse",
+        Execution::ReleaseMouse { .. } => "releasemouse",
+        Execution::Click { .. } => "click",
+        Execution::ScrollVertical { .. } => "scrollvertical",
+        Execution::ScrollHorizontal { .. } => "scrollhorizontal",
+        Execution::Drag { .. } => "drag",
+        Execution::GetMousePosition { .. } => "getmouseposition",
+        Execution::GetScreenSize { .. } => "getscreensize",
+        Execution::ReadFile { .. } => "readfile",
+        Execution::WriteFile { .. } => "writefile",
+        Execution::DeleteFile { .. } => "deletefile",
+        Execution::CreateFolder { .. } => "createfolder",
+        Execution::DeleteFolder { .. } => "deletefolder",
+        Execution::GetDataType { .. } => "getdatatype",
+        Execution::CreateArray { .. } => "createarray",
+        Execution::AddToArray { .. } => "addtoarray",
+        Execution::RemoveFromArray { .. } => "removefromarray",
+        Execution::GetArrayLength { .. } => "getarraylength",
+        Execution::LoopArray { .. } => "looparray",
+        Execution::GetArrayIndex { .. } => "getarrayindex",
+        Execution::SetArrayIndex { .. } => "setarrayindex",
+        Execution::GetFolderContents { .. } => "getfoldercontents",
+        Execution::Log { .. } => "log",
+        Execution::ClearLog { .. } => "clearlog",
+        Execution::SplitString { .. } => "splitstring",
+        Execution::JoinStrings { .. } => "joinstrings",
+        Execution::ReverseArray { .. } => "reversearray",
+        Execution::SortArray { .. } => "sortarray",
+        Execution::GetClipboard { .. } => "getclipboard",
+        Execution::RunMacro { .. } => "runmacro",
+        Execution::SetClipboard { .. } => "setclipboard",
+    }
+}
+
+pub fn find_macro_initiator(macro_name: &str, initiator_index: usize) -> Result<(Macro, Initiator), String> {
+    let macro_: Macro = get_macros().into_iter()
+        .find(|macro_| macro_.name == macro_name)
+        .ok_or(format!("There is no macro named \"{}\"", macro_name))?;
+    let initiator: Initiator = macro_.macro_.initiators.as_ref()
+        .and_then(|initiators| initiators.get(initiator_index))
+        .cloned()
+        .ok_or(format!("Macro \"{}\" has no initiator {}", macro_name, initiator_index))?;
+    Ok((macro_, initiator))
+}
+
+// Turns the fields of a JSON object, like a webhook body, into variables
+pub fn get_json_variables(value: &serde_json::Value) -> Result<Variables, String> {
+    let mut variables: Variables = Variables::new();
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields {
+                set_variable(&mut variables, name.clone(), get_json_variable_value(field));
+            }
+            Ok(variables)
+        }
+        serde_json::Value::Null => Ok(variables),
+        _ => Err("Expected a JSON object".to_string())
+    }
+}
+
+fn get_json_variable_value(value: &serde_json::Value) -> VariableValue {
+    match value {
+        serde_json::Value::Number(number) => VariableValue::Number(number.as_f64().unwrap_or(0.0)),
+        serde_json::Value::String(string) => VariableValue::String(string.clone()),
+        serde_json::Value::Bool(boolean) => VariableValue::Number(if *boolean { 1.0 } else { 0.0 }),
+        serde_json::Value::Array(array) => VariableValue::Array(array.iter().map(get_json_variable_value).collect()),
+        serde_json::Value::Null => VariableValue::String("".to_string()),
+        serde_json::Value::Object(_) => VariableValue::String(value.to_string()),
+    }
 }
 
-pub fn run_macro_function(function: Function, macro_: Macro, variables: &mut Variables) {
+// Initiators can provide values to their code. The user can rename each one, otherwise it uses the type as the name.
+pub fn get_initiator_variables(initiator: &Initiator, values: Vec<(&str, VariableValue)>) -> Variables {
+    let mut variables: Variables = Variables::new();
+    for (type_, value) in values {
+        let variable_name: String = initiator.variables.as_ref()
+            .and_then(|variables_set| variables_set.iter().find(|variable| variable.type_ == type_))
+            .map(|variable| variable.name.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(type_.to_string());
+        set_variable(&mut variables, variable_name, value);
+    }
+    variables
+}
+
+pub fn run_macro_function(function: Function, macro_: Macro, variables: &mut Variables, run: &mut MacroRun) {
     println!("Running macro function from macro \"{}\"", macro_.name);
-    execute_macro_code(&function.executes, variables, &mut false, macro_);
+    let function_index: usize = macro_.macro_.functions.iter().flatten()
+        .position(|other_function| other_function.name == function.name)
+        .unwrap_or(0);
+    // Shared functions can run from any macro, so the path says which macro the function belongs to
+    let caller_path: Vec<String> = std::mem::replace(&mut run.path, vec![format!("function:{}:{}", macro_.name, function_index)]);
+    execute_macro_code(&function.executes, variables, run, macro_);
+    run.path = caller_path;
+    // A stop block inside a function only stops the function
+    run.stop_execution = false;
 }
 
 
-type Variables = HashMap<String, Variable>;
+pub type Variables = HashMap<String, Variable>;
 
 #[derive(Debug, Clone)]
 pub struct Variable {
@@ -467,7 +845,8 @@ impl Variable {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
 pub enum VariableValue {
     String(String),
     Number(f64),
@@ -555,15 +934,50 @@ fn get_variable_vector(variable_value: VariableValue) -> Vec<VariableValue> {
     }
 }
 
-fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_execution: &mut bool, macro_: Macro) {
-    for execution in code {
-        if *stop_execution {
-            return;
+fn execute_macro_code(code: &[Execution], variables: &mut Variables, run: &mut MacroRun, macro_: Macro) {
+    run.path.push(String::new());
+    // Trace entries get their duration once the next execution starts, so it doesn't matter how the last one ended
+    let mut traced: Option<(usize, Instant)> = None;
+    for (index, execution) in code.iter().enumerate() {
+        finish_trace_entry(run, traced.take());
+        if run.should_stop() {
+            break;
+        }
+
+        if let Some(path_index) = run.path.last_mut() {
+            *path_index = index.to_string();
+        }
+        if let Some(debugger) = run.debugger.as_mut() {
+            if !debugger.before_execution(run.id, &run.path.join("/"), execution, variables) {
+                stop_run(run.id);
+                break;
+            }
+        }
+        if let Some(trace) = run.trace.as_mut() {
+            if trace.len() < MAX_TRACE_ENTRIES {
+                trace.push(TraceEntry {
+                    path: run.path.join("/"),
+                    execution: get_execution_type(execution).to_string(),
+                    started: get_timestamp().saturating_sub(run.started),
+                    duration: 0,
+                });
+                traced = Some((trace.len() - 1, Instant::now()));
+            }
+        }
+
+        if let Some(actions) = run.dry_run.as_mut() {
+            if let Some(action) = get_dry_run_action(execution, variables) {
+                actions.push(action);
+                // Keyboard and mouse input goes to a recording backend in a dry run, so it still runs to record the exact events
+                if !is_input_execution(execution) {
+                    continue;
+                }
+            }
         }
 
         match execution {
             Execution::Wait { data } => {
-                thread::sleep(std::time::Duration::from_millis((data.time * 1000.0) as u64));
+                run.sleep(std::time::Duration::from_millis((data.time * 1000.0) as u64));
             }
             Execution::Notification { data } => {
                 let _ = Notification::new("code-macros")
@@ -585,20 +999,20 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
                 if data.end > data.start {
                     while i <= data.end {
                         set_variable(variables, variable_name.to_string().clone(), VariableValue::Number(i));
-                        execute_macro_code(&code_inside.loop_.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                        execute_code_inside("loop", &code_inside.loop_, variables, run, macro_.clone());
                         i += data.step;
                         iterations += 1;
-                        if iterations > MAX_LOOP_ITERATIONS {
+                        if iterations > MAX_LOOP_ITERATIONS || run.should_stop() {
                             break;
                         }
                     }
                 } else {
                     while i >= data.end {
                         set_variable(variables, variable_name.to_string().clone(), VariableValue::Number(i));
-                        execute_macro_code(&code_inside.loop_.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                        execute_code_inside("loop", &code_inside.loop_, variables, run, macro_.clone());
                         i += data.step;
                         iterations += 1;
-                        if iterations > MAX_LOOP_ITERATIONS {
+                        if iterations > MAX_LOOP_ITERATIONS || run.should_stop() {
                             break;
                         }
                     }
@@ -618,22 +1032,22 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
                 while get_condition_bool(evaluate_condition(&data.condition, variables)) {
                     set_variable(variables, variable_name.to_string().clone(), VariableValue::Number(i as f64));
                     i += 1;
-                    execute_macro_code(&code_inside.loop_.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                    execute_code_inside("loop", &code_inside.loop_, variables, run, macro_.clone());
                     
-                    if i > MAX_LOOP_ITERATIONS {
+                    if i > MAX_LOOP_ITERATIONS || run.should_stop() {
                         break;
                     }
                 }
             }
             Execution::If { data, code_inside } => {
                 if get_condition_bool(evaluate_condition(&data.condition, variables)) {
-                    execute_macro_code(&code_inside.then.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                    execute_code_inside("then", &code_inside.then, variables, run, macro_.clone());
                 } else {
-                    execute_macro_code(&code_inside.else_.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                    execute_code_inside("else", &code_inside.else_, variables, run, macro_.clone());
                 }
             }
             Execution::Stop {  } => {
-                *stop_execution = true;
+                run.stop_execution = true;
             }
             Execution::SetVariable { data } => {
                 set_variable(variables, data.variable.to_string().clone(), VariableValue::Number(
@@ -641,79 +1055,159 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
                 ));
             }
             Execution::Function { data } => {
-                for function in &macro_.clone().macro_.functions.unwrap() {
-                    if function.name == *data.function {
-                        run_macro_function(function.clone(), macro_.clone(), variables);
-                        break;
+                match resolve_function(&macro_, &data.function) {
+                    Ok((function, function_macro)) => {
+                        run_macro_function(function, function_macro, variables, run);
+                    }
+                    Err(error) => {
+                        println!("{}", error);
+                        write_log_entry(&macro_.name, "error", &error);
                     }
                 }
             }
             Execution::TypeString { data } => {
-                // FIXME: Yes, this is really, really bad code. It is intentionally creating a memory leak.
-                let string_static = Box::leak(parse_string(&data.string.clone(), &mut variables.clone()).into_boxed_str());
+                let string: String = parse_string(&data.string, variables);
 
-                KeySequence(string_static).send();
+                type_string(&string, data.braces == "keys", std::time::Duration::from_millis(data.delay.max(0.0) as u64), run);
             }
             Execution::MouseMoveRelative { data } => {
-                MouseCursor::move_rel(data.x.round() as i32, data.y.round() as i32);
+                run.input().move_mouse_relative(evaluate_number(&data.x, variables).round() as i32, evaluate_number(&data.y, variables).round() as i32);
             }
             Execution::MouseMoveAbsolute { data } => {
-                MouseCursor::move_abs(data.x.round() as i32, data.y.round() as i32);
+                run.input().move_mouse_absolute(evaluate_number(&data.x, variables).round() as i32, evaluate_number(&data.y, variables).round() as i32);
             }
             Execution::PressKey { data } => {
-                let key_char: char = data.key.chars().nth(0).unwrap();
+                let key_char: char = match data.key.chars().next() {
+                    Some(key_char) => key_char,
+                    None => {
+                        println!("No key to press");
+                        continue;
+                    }
+                };
+                let keybd_key: KeybdKey = match get_keybd_key(key_char) {
+                    Some(keybd_key) => keybd_key,
+                    None => {
+                        println!("There's no key for \"{}\"", key_char);
+                        continue;
+                    }
+                };
 
                 // Check if the key pressed requires shift to be held
-                if key_char.is_uppercase() || [ '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '{', '}', '|', ':', '"', '<', '>', '?', '~' ].contains(&key_char) {
-                    KeybdKey::LShiftKey.press();
-                }
-
-                let keybd_key: Option<KeybdKey> = get_keybd_key(key_char);
-                
-                match keybd_key {
-                    Some(press) => {
-                        press.press();
-                    }
-                    None => { unimplemented!() }
+                if needs_shift(key_char) {
+                    run.input().press_key(KeybdKey::LShiftKey);
                 }
+                run.input().press_key(keybd_key);
             }
             Execution::ReleaseKey { data } => {
-                let key_char: char = data.key.chars().nth(0).unwrap();
+                let key_char: char = match data.key.chars().next() {
+                    Some(key_char) => key_char,
+                    None => {
+                        println!("No key to release");
+                        continue;
+                    }
+                };
+                let keybd_key: KeybdKey = match get_keybd_key(key_char) {
+                    Some(keybd_key) => keybd_key,
+                    None => {
+                        println!("There's no key for \"{}\"", key_char);
+                        continue;
+                    }
+                };
 
                 // Check if the key pressed requires shift to be held
-                if key_char.is_uppercase() || [ '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '{', '}', '|', ':', '"', '<', '>', '?', '~' ].contains(&key_char) {
-                    KeybdKey::LShiftKey.release();
+                if needs_shift(key_char) {
+                    run.input().release_key(KeybdKey::LShiftKey);
+                }
+                run.input().release_key(keybd_key);
+            }
+            Execution::SendHotkey { data } => {
+                match parse_hotkey(&parse_string(&data.keys, variables)) {
+                    Ok(keys) => tap_keys(&mut *run.input(), &keys),
+                    Err(error) => println!("{}", error)
+                }
+            }
+            Execution::PressMouse { data } => {
+                match get_named_button(&data.button) {
+                    Some(button) => run.input().press_button(button),
+                    None => println!("Unknown mouse button \"{}\"", data.button)
+                }
+            }
+            Execution::ReleaseMouse { data } => {
+                match get_named_button(&data.button) {
+                    Some(button) => run.input().release_button(button),
+                    None => println!("Unknown mouse button \"{}\"", data.button)
                 }
+            }
+            Execution::Click { data } => {
+                let button: MouseButton = match get_named_button(&data.button) {
+                    Some(button) => button,
+                    None => {
+                        println!("Unknown mouse button \"{}\"", data.button);
+                        continue;
+                    }
+                };
 
-                let keybd_key: Option<KeybdKey> = get_keybd_key(key_char);
-                
-                match keybd_key {
-                    Some(press) => {
-                        press.release();
+                for click in 0..get_click_count(data) {
+                    // Clicks have to be close together to count as a double or triple click
+                    if click > 0 {
+                        run.sleep(std::time::Duration::from_millis(CLICK_INTERVAL));
                     }
-                    None => { unimplemented!() }
+                    if run.should_stop() {
+                        break;
+                    }
+                    run.input().press_button(button);
+                    run.input().release_button(button);
                 }
             }
-            Execution::PressMouse { data } => {
-                match data.button.as_str() {
-                    "LMB" => {
-                        MouseButton::LeftButton.press();
+            Execution::ScrollVertical { data } => {
+                run.input().scroll_vertical(data.amount.round() as i32);
+            }
+            Execution::ScrollHorizontal { data } => {
+                run.input().scroll_horizontal(data.amount.round() as i32);
+            }
+            Execution::Drag { data } => {
+                let from: (f64, f64) = (evaluate_number(&data.fromx, variables), evaluate_number(&data.fromy, variables));
+                let to: (f64, f64) = (evaluate_number(&data.tox, variables), evaluate_number(&data.toy, variables));
+                match get_named_button(&data.button) {
+                    Some(button) => drag_mouse(button, from, to, data.duration, run),
+                    None => println!("Unknown mouse button \"{}\"", data.button)
+                }
+            }
+            Execution::GetMousePosition { data } => {
+                let (x, y) = match run.input().get_mouse_position() {
+                    Ok(position) => position,
+                    Err(error) => {
+                        println!("{}", error);
+                        continue;
                     }
-                    "RMB" => {
-                        MouseButton::RightButton.press();
+                };
+
+                let values: [(&String, f64); 2] = [(&data.x, x as f64), (&data.y, y as f64)];
+                for (variable, value) in values {
+                    if !variable.is_empty() {
+                        set_variable(variables, variable.to_string(), VariableValue::Number(value));
                     }
-                    _ => todo!()
                 }
             }
-            Execution::ReleaseMouse { data } => {
-                match data.button.as_str() {
-                    "LMB" => {
-                        MouseButton::LeftButton.release();
+            Execution::GetScreenSize { data } => {
+                let bounds: ScreenBounds = match get_screen_bounds(&data.monitor, &*run.input()) {
+                    Ok(bounds) => bounds,
+                    Err(error) => {
+                        println!("{}", error);
+                        continue;
                     }
-                    "RMB" => {
-                        MouseButton::RightButton.release();
+                };
+
+                let values: [(&String, f64); 4] = [
+                    (&data.width, bounds.width as f64),
+                    (&data.height, bounds.height as f64),
+                    (&data.x, bounds.x as f64),
+                    (&data.y, bounds.y as f64),
+                ];
+                for (variable, value) in values {
+                    if !variable.is_empty() {
+                        set_variable(variables, variable.to_string(), VariableValue::Number(value));
                     }
-                    _ => todo!()
                 }
             }
             Execution::ReadFile { data } => {
@@ -797,7 +1291,10 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
                 for (_index, value) in list_content.iter().enumerate() {
                     set_variable(variables, variable_name.clone(), value.clone());
 
-                    execute_macro_code(&code_inside.loop_.as_ref().unwrap_or_default().executes, variables, stop_execution, macro_.clone());
+                    execute_code_inside("loop", &code_inside.loop_, variables, run, macro_.clone());
+                    if run.should_stop() {
+                        break;
+                    }
                 }
             }
             Execution::GetArrayIndex { data } => {
@@ -833,20 +1330,12 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
                 set_variable(variables, data.output.to_string().clone(), VariableValue::Array(list_content));
             }
             Execution::Log { data } => {
-                let message: String = data.message.clone();
-
-                let current_log_content: String = get_log_content().unwrap();
+                let message: String = parse_string(&data.message, variables);
 
-                let new_log_content: String = format!("{}{}\n", current_log_content, message);
-
-                write_to_log(new_log_content);
-                
-                update_log_page();
+                write_log_entry(&macro_.name, &data.level, &message);
             }
             Execution::ClearLog { } => {
-                write_to_log("".to_string());
-
-                update_log_page();
+                clear_log(&macro_.name);
             }
             Execution::SplitString { data } => {
                 let string_content: String = parse_string(&data.string, variables);
@@ -891,8 +1380,107 @@ fn execute_macro_code(code: &Vec<Execution>, variables: &mut Variables, stop_exe
 
                 set_variable(variables, data.output.to_string().clone(), VariableValue::Array(new_list_content));
             }
+            Execution::GetClipboard { data } => {
+                let clipboard_content: String = get_clipboard_text().unwrap_or("".to_string());
+
+                set_variable(variables, data.output.to_string().clone(), VariableValue::String(clipboard_content));
+            }
+            Execution::SetClipboard { data } => {
+                let clipboard_content: String = parse_string(&data.content, variables);
+
+                if let Err(error) = set_clipboard_text(clipboard_content) {
+                    println!("Failed to set the clipboard: {}", error);
+                }
+            }
+            Execution::RunMacro { data } => {
+                if run.depth >= MAX_CHAIN_DEPTH {
+                    println!("Not running macro \"{}\" because too many macros started each other", data.macro_);
+                    continue;
+                }
+
+                let initiator_index: usize = data.initiator.max(0.0) as usize;
+                let (other_macro, initiator) = match find_macro_initiator(&data.macro_, initiator_index) {
+                    Ok(found) => found,
+                    Err(error) => {
+                        println!("{}", error);
+                        continue;
+                    }
+                };
+                if !is_initiator_active(&initiator, &other_macro) {
+                    println!("Not running macro \"{}\" because it or its initiator is disabled, or macros are paused", data.macro_);
+                    continue;
+                }
+
+                let mut inputs: Variables = Variables::new();
+                for input in data.inputs.split(',').map(|input| input.trim()).filter(|input| !input.is_empty()) {
+                    if let Some(variable) = get_variable(variables, input.to_string()) {
+                        set_variable(&mut inputs, input.to_string(), variable.value.clone());
+                    }
+                }
+
+                let (_, receiver) = start_chained_macro_run(initiator, initiator_index, other_macro, inputs, "macro".to_string(), run.depth + 1);
+                if data.wait != "wait" {
+                    continue;
+                }
+
+                // Stopping this run stops waiting, but the other macro keeps going
+                let result: Option<MacroResult> = loop {
+                    match receiver.recv_timeout(std::time::Duration::from_millis(50)) {
+                        Ok(result) => break Some(result),
+                        Err(RecvTimeoutError::Timeout) if !run.should_stop() => {}
+                        Err(_) => break None
+                    }
+                };
+
+                // The other macro's "result" variable is its result
+                if let Some(result) = result {
+                    let result_value: VariableValue = match result.variables.get("result") {
+                        Some(variable) if result.success => variable.value.clone(),
+                        _ => VariableValue::String("".to_string())
+                    };
+                    set_variable(variables, data.output.to_string().clone(), result_value);
+                }
+            }
+        }
+    }
+    finish_trace_entry(run, traced.take());
+    run.path.pop();
+}
+
+// Moves in small steps while the button is held, so apps see the mouse being dragged instead of jumping to the end
+fn drag_mouse(button: MouseButton, from: (f64, f64), to: (f64, f64), duration: f64, run: &MacroRun) {
+    let duration: std::time::Duration = std::time::Duration::from_secs_f64(duration.max(0.0));
+    let steps: u32 = ((duration.as_millis() / DRAG_STEP_INTERVAL as u128) as u32).max(MIN_DRAG_STEPS);
+
+    run.input().move_mouse_absolute(from.0.round() as i32, from.1.round() as i32);
+    run.input().press_button(button);
+    for step in 1..=steps {
+        run.sleep((duration / steps).max(std::time::Duration::from_millis(MIN_DRAG_STEP_INTERVAL)));
+        if run.should_stop() {
+            break;
         }
+        let progress: f64 = step as f64 / steps as f64;
+        run.input().move_mouse_absolute(
+            (from.0 + (to.0 - from.0) * progress).round() as i32,
+            (from.1 + (to.1 - from.1) * progress).round() as i32
+        );
     }
+    run.input().release_button(button);
+}
+
+fn finish_trace_entry(run: &mut MacroRun, traced: Option<(usize, Instant)>) {
+    if let (Some(trace), Some((trace_index, started))) = (run.trace.as_mut(), traced) {
+        if let Some(entry) = trace.get_mut(trace_index) {
+            entry.duration = started.elapsed().as_millis() as u64;
+        }
+    }
+}
+
+// Runs one of the blocks inside an execution, like the "then" of an if
+fn execute_code_inside(key: &str, code_inside: &Option<ExecutionWrapper>, variables: &mut Variables, run: &mut MacroRun, macro_: Macro) {
+    run.path.push(key.to_string());
+    execute_macro_code(&code_inside.as_ref().unwrap_or_default().executes, variables, run, macro_);
+    run.path.pop();
 }
 
 fn evaluate_condition(condition: &Condition, variables: &mut Variables) -> Condition {
@@ -1020,6 +1608,13 @@ fn evaluate_expression(expression: &Expression, variables: &mut Variables) -> Ex
     }
 }
 
+pub fn evaluate_number(number: &NumberExpression, variables: &mut Variables) -> f64 {
+    match number {
+        NumberExpression::Number(value) => *value,
+        NumberExpression::Expression(expression) => get_expression_number(evaluate_expression(expression, variables))
+    }
+}
+
 fn get_expression_number(value: Expression) -> f64 {
     match value {
         Expression::Number { value } => {
@@ -1063,7 +1658,7 @@ fn get_condition_number(value: Condition) -> f64 {
     }
 }
 
-fn parse_string<'a>(string: &'a String, variables: &'a mut Variables) -> String {
+pub fn parse_string<'a>(string: &'a String, variables: &'a mut Variables) -> String {
     let variable_split: Vec<&str> = string.split("{{").collect();
     let mut result = String::from(variable_split[0]);
     let mut index: u64 = 0;
@@ -1116,76 +1711,163 @@ fn get_variable(variables: &mut Variables, variable: String) -> Option<&Variable
     return variables.get(&variable);
 }
 
-fn get_log_path() -> Option<String> {
-    // TODO: Do this in a better way
-    let app_handle: Option<AppHandle> = get_app_handle();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::use_test_macros;
+    use super::super::paths::use_test_data_dir;
 
-    match app_handle {
-        Some(app_handle) => {
-            let path_resolver: PathResolver = app_handle.path_resolver();
-            let log_path: String = path_resolver.log_dir().unwrap().to_str().unwrap().to_string();
+    use serde_json::json;
 
-            let path: &Path = Path::new(&log_path);
-            let parent: &Path = path.parent().unwrap().parent().unwrap();
+    // Runs the code with its input recorded, and returns the input including what's released when the run ends
+    fn get_inputs(code: serde_json::Value) -> Vec<InputEvent> {
+        use_test_data_dir();
+        let code: Vec<Execution> = serde_json::from_value(code).unwrap();
+        let macro_: Macro = serde_json::from_value(json!({
+            "name": "Input test", "description": "", "macro_": { "initiators": [], "functions": null }
+        })).unwrap();
 
-            // TODO: Add multiple logs
-            let mut folder = parent.to_path_buf();
-            folder.push("CodeMacros");
-            folder.push("Logs");
-            folder.push("log.txt");
+        let recorder: RecordingBackend = RecordingBackend::default();
+        let events: Arc<Mutex<Vec<InputEvent>>> = recorder.get_events();
+        let mut run: MacroRun = start_run(&macro_.name, "test");
+        run.set_input_backend(Box::new(recorder));
+        execute_macro_code(&code, &mut Variables::new(), &mut run, macro_);
+        drop(run);
 
-            // println!("{}", folder.to_str().unwrap());
-            let folder_path: String = folder.to_str().unwrap().to_string();
+        let inputs: Vec<InputEvent> = events.lock().unwrap().clone();
+        inputs
+    }
 
-            return Some(folder_path);
-        }
-        None => {
-            println!("Waiting to get app handle for macro to execute...");
-            return None;
-        }
+    fn press_key(key: &str) -> InputEvent {
+        InputEvent::PressKey { key: key.to_string() }
     }
-}
 
-fn get_log_content() -> Option<String> {
-    let log_path: Option<String> = get_log_path();
+    fn release_key(key: &str) -> InputEvent {
+        InputEvent::ReleaseKey { key: key.to_string() }
+    }
 
-    match log_path {
-        Some(log_path) => {
-            let content: String = fs::read_to_string(log_path).unwrap_or(String::from(""));
-            return Some(content);
-        }
-        None => {
-            return None;
-        }
+    fn press_button(button: &str) -> InputEvent {
+        InputEvent::PressButton { button: button.to_string() }
     }
-}
 
-fn write_to_log(content: String) {
-    let log_path: Option<String> = get_log_path();
+    fn release_button(button: &str) -> InputEvent {
+        InputEvent::ReleaseButton { button: button.to_string() }
+    }
 
-    match log_path {
-        Some(log_path) => {
-            // Make sure the log_path folder exists
-            fs::create_dir_all(Path::new(&log_path).parent().unwrap()).unwrap();
-            fs::write(log_path, content).unwrap();
-        }
-        None => {
-            // Do nothing
-        }
+    #[test]
+    fn types_strings() {
+        assert_eq!(get_inputs(json!([{ "type_": "typestring", "data": { "string": "Hi é{enter}", "braces": "keys" } }])), vec![
+            press_key("shift"), press_key("h"), release_key("h"), release_key("shift"),
+            press_key("i"), release_key("i"),
+            press_key("space"), release_key("space"),
+            InputEvent::TypeCharacter { character: 'é' },
+            press_key("enter"), release_key("enter"),
+        ]);
     }
-}
 
-fn update_log_page() {
-    let app_handle: Option<AppHandle> = get_app_handle();
+    #[test]
+    fn sends_hotkeys() {
+        assert_eq!(get_inputs(json!([{ "type_": "sendhotkey", "data": { "keys": "ctrl+shift+t" } }])), vec![
+            press_key("control"), press_key("shift"), press_key("t"),
+            release_key("t"), release_key("shift"), release_key("control"),
+        ]);
+    }
 
-    match app_handle {
-        Some(app_handle) => {
-            // Update the log if the user is currently on the log page
-            let window: Window = app_handle.get_window("main").unwrap();
-            window.eval("document.updateLog()").unwrap();
-        }
-        None => {
-            // Do nothing
+    #[test]
+    fn clicks() {
+        assert_eq!(get_inputs(json!([{ "type_": "click", "data": { "button": "RMB", "count": 3 } }])), vec![
+            press_button("RMB"), release_button("RMB"),
+            press_button("RMB"), release_button("RMB"),
+            press_button("RMB"), release_button("RMB"),
+        ]);
+        assert_eq!(get_inputs(json!([{ "type_": "click", "data": { "button": "LMB", "count": 1000 } }])).len(), MAX_CLICKS as usize * 2);
+    }
+
+    #[test]
+    fn drags() {
+        let mut expected: Vec<InputEvent> = vec![InputEvent::MoveMouseAbsolute { x: 10, y: 20 }, press_button("LMB")];
+        for step in 1..=MIN_DRAG_STEPS as i32 {
+            expected.push(InputEvent::MoveMouseAbsolute { x: 10 + step * 100 / MIN_DRAG_STEPS as i32, y: 20 });
         }
+        expected.push(release_button("LMB"));
+
+        assert_eq!(get_inputs(json!([{ "type_": "drag", "data": {
+            "button": "LMB", "fromx": 10, "fromy": 20, "tox": 110, "toy": 20, "duration": 0
+        } }])), expected);
+    }
+
+    #[test]
+    fn scrolls() {
+        assert_eq!(get_inputs(json!([
+            { "type_": "scrollvertical", "data": { "amount": -2.4 } },
+            { "type_": "scrollhorizontal", "data": { "amount": 3 } }
+        ])), vec![InputEvent::ScrollVertical { amount: -2 }, InputEvent::ScrollHorizontal { amount: 3 }]);
+    }
+
+    #[test]
+    fn releases_what_the_run_left_pressed() {
+        assert_eq!(get_inputs(json!([
+            { "type_": "presskey", "data": { "key": "A" } },
+            { "type_": "pressmouse", "data": { "button": "MMB" } },
+            { "type_": "presskey", "data": { "key": "" } }
+        ])), vec![
+            press_key("shift"), press_key("a"), press_button("MMB"),
+            release_key("a"), release_key("shift"), release_button("MMB"),
+        ]);
+    }
+
+    // Runs a macro that calls "Chain target", which returns its "count" input plus one, and returns what it output
+    fn run_other_macro(target: &str, wait: &str, depth: u32) -> Option<f64> {
+        use_test_data_dir();
+        let macros: Vec<Macro> = serde_json::from_value(json!([
+            { "name": "Chain target", "description": "", "macro_": { "initiators": [
+                { "type_": "manual", "data": {}, "executes": [
+                    { "type_": "setvariable", "data": { "variable": "result", "content": {
+                        "type_": "arithmetic", "kind": "addition",
+                        "left": { "type_": "variable", "variable": "count" }, "right": { "type_": "number", "value": 1 }
+                    } } }
+                ], "variables": null }
+            ], "functions": null } },
+            { "name": "Chain disabled", "description": "", "enabled": false, "macro_": { "initiators": [
+                { "type_": "manual", "data": {}, "executes": [], "variables": null }
+            ], "functions": null } }
+        ])).unwrap();
+        let _macros_guard = use_test_macros(macros);
+
+        let code: Vec<Execution> = serde_json::from_value(json!([
+            { "type_": "runmacro", "data": { "macro_": target, "initiator": 0, "inputs": " count, missing", "wait": wait, "output": "out" } }
+        ])).unwrap();
+        let macro_: Macro = serde_json::from_value(json!({
+            "name": "Chain caller", "description": "", "macro_": { "initiators": [], "functions": null }
+        })).unwrap();
+
+        let mut variables: Variables = Variables::new();
+        set_variable(&mut variables, "count".to_string(), VariableValue::Number(2.0));
+        let mut run: MacroRun = start_run(&macro_.name, "test");
+        run.depth = depth;
+        execute_macro_code(&code, &mut variables, &mut run, macro_);
+        get_variable(&mut variables, "out".to_string()).map(|variable| get_variable_number(variable.value.clone()))
+    }
+
+    #[test]
+    fn waits_for_other_macros_with_inputs() {
+        assert_eq!(run_other_macro("Chain target", "wait", 0), Some(3.0));
+        assert_eq!(run_other_macro("Chain target", "wait", MAX_CHAIN_DEPTH - 1), Some(3.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn doesnt_wait_for_other_macros_unless_asked() {
+        assert_eq!(run_other_macro("Chain target", "background", 0), None);
+    }
+
+    #[test]
+    fn limits_how_deep_macros_run_each_other() {
+        assert_eq!(run_other_macro("Chain target", "wait", MAX_CHAIN_DEPTH), None);
+    }
+
+    #[test]
+    fn doesnt_run_inactive_or_missing_macros() {
+        assert_eq!(run_other_macro("Chain disabled", "wait", 0), None);
+        assert_eq!(run_other_macro("Chain missing", "wait", 0), None);
+    }
+}
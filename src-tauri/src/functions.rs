@@ -0,0 +1,164 @@
+use super::{ get_macros, Macro };
+use super::execute::{ Execution, Function };
+
+// Functions marked as shared make up a library that every macro can call.
+// A macro's own functions come first, so a macro can replace a shared function with its own version.
+pub fn resolve_function(macro_: &Macro, name: &str) -> Result<(Function, Macro), String> {
+    resolve_function_in(&get_macros(), macro_, name)
+}
+
+fn resolve_function_in(macros: &[Macro], macro_: &Macro, name: &str) -> Result<(Function, Macro), String> {
+    if name.is_empty() {
+        return Err(format!("Macro \"{}\" calls a function without choosing one", macro_.name));
+    }
+
+    let own_functions: Vec<&Function> = macro_.macro_.functions.iter().flatten()
+        .filter(|function| function.name == name)
+        .collect();
+    match own_functions.len() {
+        0 => {}
+        1 => return Ok((own_functions[0].clone(), macro_.clone())),
+        _ => return Err(format!("Macro \"{}\" has more than one function named \"{}\"", macro_.name, name))
+    }
+
+    // Shared functions run as part of the macro they're from, so the functions they call are looked up there
+    let shared_functions: Vec<(&Function, &Macro)> = macros.iter()
+        .filter(|other_macro| other_macro.name != macro_.name)
+        .flat_map(|other_macro| {
+            other_macro.macro_.functions.iter().flatten()
+                .filter(|function| function.shared && function.name == name)
+                .map(move |function| (function, other_macro))
+        })
+        .collect();
+    match shared_functions.len() {
+        0 => Err(format!("Macro \"{}\" calls \"{}\", but there is no function with that name", macro_.name, name)),
+        1 => Ok((shared_functions[0].0.clone(), shared_functions[0].1.clone())),
+        _ => Err(format!(
+            "Macro \"{}\" calls \"{}\", but it's shared by more than one macro ({})",
+            macro_.name,
+            name,
+            shared_functions.iter().map(|(_, other_macro)| format!("\"{}\"", other_macro.name)).collect::<Vec<String>>().join(", ")
+        ))
+    }
+}
+
+// Finds function calls that can't be resolved, so they can be shown before the macro runs
+pub fn validate_function_calls(macros: &[Macro]) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+
+    for macro_ in macros {
+        let mut calls: Vec<String> = Vec::new();
+        for initiator in macro_.macro_.initiators.iter().flatten() {
+            get_function_calls(&initiator.executes, &mut calls);
+        }
+        for function in macro_.macro_.functions.iter().flatten() {
+            get_function_calls(&function.executes, &mut calls);
+        }
+
+        calls.sort();
+        calls.dedup();
+        for call in calls {
+            if let Err(problem) = resolve_function_in(macros, macro_, &call) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    problems
+}
+
+fn get_function_calls(code: &Vec<Execution>, calls: &mut Vec<String>) {
+    for execution in code {
+        match execution {
+            Execution::Function { data } => calls.push(data.function.clone()),
+            Execution::If { code_inside, .. }
+            | Execution::FromToLoop { code_inside, .. }
+            | Execution::WhileLoop { code_inside, .. }
+            | Execution::LoopArray { code_inside, .. } => {
+                for wrapper in [&code_inside.loop_, &code_inside.then, &code_inside.else_].into_iter().flatten() {
+                    get_function_calls(&wrapper.executes, calls);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn call(name: &str) -> serde_json::Value {
+        json!({ "type_": "function", "data": { "function": name } })
+    }
+
+    fn function(name: &str, shared: bool) -> serde_json::Value {
+        json!({ "name": name, "parameters": [], "executes": [], "shared": shared })
+    }
+
+    fn get_test_macros() -> Vec<Macro> {
+        serde_json::from_value(json!([
+            { "name": "Caller", "description": "", "macro_": { "initiators": [
+                { "type_": "manual", "data": {}, "executes": [
+                    call("own"), call("shared"), call("missing"),
+                    { "type_": "if", "data": { "condition": { "type_": "boolean", "value": true } }, "code_inside": {
+                        "loop_": null, "then": { "executes": [call("ambiguous")] }, "else_": { "executes": [call("")] }
+                    } }
+                ], "variables": null }
+            ], "functions": [function("own", false), function("private", false)] } },
+            { "name": "Library", "description": "", "macro_": { "initiators": null, "functions": [
+                function("own", true), function("shared", true), function("ambiguous", true), function("private", false)
+            ] } },
+            { "name": "Other library", "description": "", "macro_": { "initiators": null, "functions": [
+                function("ambiguous", true), function("twice", false), function("twice", false)
+            ] } }
+        ])).unwrap()
+    }
+
+    fn resolve(macros: &[Macro], name: &str) -> Result<String, String> {
+        resolve_function_in(macros, &macros[0], name).map(|(function, macro_)| format!("{} from {}", function.name, macro_.name))
+    }
+
+    #[test]
+    fn prefers_the_macros_own_functions() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(resolve(&macros, "own"), Ok("own from Caller".to_string()));
+        assert_eq!(resolve(&macros, "private"), Ok("private from Caller".to_string()));
+    }
+
+    #[test]
+    fn finds_shared_functions() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(resolve(&macros, "shared"), Ok("shared from Library".to_string()));
+        // Functions that aren't shared can't be called from other macros
+        assert!(resolve_function_in(&macros, &macros[1], "twice").is_err());
+    }
+
+    #[test]
+    fn rejects_ambiguous_and_missing_functions() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(
+            resolve(&macros, "ambiguous"),
+            Err("Macro \"Caller\" calls \"ambiguous\", but it's shared by more than one macro (\"Library\", \"Other library\")".to_string())
+        );
+        assert_eq!(
+            resolve(&macros, "missing"),
+            Err("Macro \"Caller\" calls \"missing\", but there is no function with that name".to_string())
+        );
+        assert_eq!(
+            resolve_function_in(&macros, &macros[2], "twice").map(|_| ()),
+            Err("Macro \"Other library\" has more than one function named \"twice\"".to_string())
+        );
+    }
+
+    #[test]
+    fn validates_calls_in_nested_code() {
+        assert_eq!(validate_function_calls(&get_test_macros()), vec![
+            "Macro \"Caller\" calls a function without choosing one".to_string(),
+            "Macro \"Caller\" calls \"ambiguous\", but it's shared by more than one macro (\"Library\", \"Other library\")".to_string(),
+            "Macro \"Caller\" calls \"missing\", but there is no function with that name".to_string(),
+        ]);
+    }
+}
@@ -0,0 +1,224 @@
+use serde::{ Deserialize, Serialize };
+
+use std::fs::{ self, OpenOptions };
+use std::io::{ self, Write };
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use super::paths::get_history_path;
+
+// Only the most recent runs are kept, so the history file can't grow forever
+static MAX_HISTORY_RUNS: usize = 200;
+pub static MAX_TRACE_ENTRIES: usize = 1000;
+
+// Loaded from disk the first time it's needed
+static HISTORY: Lazy<Mutex<Option<History>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    pub id: u64,
+    pub macro_name: String,
+    pub trigger: String,
+    // Milliseconds since the Unix epoch
+    pub started: u64,
+    pub ended: u64,
+    // "succeeded", "failed" or "stopped"
+    pub outcome: String,
+    pub error: Option<String>,
+    // Only recorded if tracing is turned on in the settings
+    pub trace: Option<Vec<TraceEntry>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEntry {
+    // The node path of the execution, like "initiator:0/2/then/1"
+    pub path: String,
+    pub execution: String,
+    // Milliseconds since the run started
+    pub started: u64,
+    // How long the execution took in milliseconds, including the code inside it
+    pub duration: u64,
+}
+
+// Runs are appended to the file one line at a time. Once it has a lot more lines than are kept,
+// it's rewritten with only the kept ones.
+struct History {
+    // None if there's no data folder, in which case runs are only kept in memory
+    path: Option<PathBuf>,
+    runs: Vec<RunRecord>,
+    lines: usize,
+}
+
+fn load_history() -> History {
+    match get_history_path() {
+        Some(path) => load_history_file(path),
+        None => History { path: None, runs: Vec::new(), lines: 0 }
+    }
+}
+
+fn load_history_file(path: PathBuf) -> History {
+    // History used to be saved as one JSON array
+    let old_path: PathBuf = path.with_extension("json");
+    if !path.exists() && old_path.exists() {
+        let runs: Vec<RunRecord> = fs::read_to_string(&old_path).ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let mut history: History = History { path: Some(path.clone()), runs, lines: 0 };
+        compact_history(&mut history);
+        if path.exists() {
+            let _ = fs::remove_file(&old_path);
+        }
+        return history;
+    }
+
+    let content: String = fs::read_to_string(&path).unwrap_or_default();
+    let mut runs: Vec<RunRecord> = content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    let lines: usize = runs.len();
+    if runs.len() > MAX_HISTORY_RUNS {
+        let extra: usize = runs.len() - MAX_HISTORY_RUNS;
+        runs.drain(..extra);
+    }
+    History { path: Some(path), runs, lines }
+}
+
+fn write_history_file(path: &Option<PathBuf>, runs: &[RunRecord], append: bool) -> io::Result<()> {
+    let path: &PathBuf = path.as_ref().ok_or(io::Error::new(io::ErrorKind::NotFound, "There's no data folder"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut content: String = String::new();
+    for run in runs {
+        content.push_str(&serde_json::to_string(run).unwrap_or_default());
+        content.push('\n');
+    }
+    let mut file: fs::File = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+    file.write_all(content.as_bytes())
+}
+
+fn compact_history(history: &mut History) {
+    match write_history_file(&history.path, &history.runs, false) {
+        Ok(()) => history.lines = history.runs.len(),
+        Err(error) => println!("Failed to save the run history: {}", error)
+    }
+}
+
+pub fn record_run(record: RunRecord) {
+    let mut history = HISTORY.lock().unwrap();
+    add_run(history.get_or_insert_with(load_history), record);
+}
+
+fn add_run(history: &mut History, record: RunRecord) {
+    match write_history_file(&history.path, std::slice::from_ref(&record), true) {
+        Ok(()) => history.lines += 1,
+        Err(error) => println!("Failed to save the run history: {}", error)
+    }
+
+    history.runs.push(record);
+    if history.runs.len() > MAX_HISTORY_RUNS {
+        let extra: usize = history.runs.len() - MAX_HISTORY_RUNS;
+        history.runs.drain(..extra);
+    }
+    if history.lines >= MAX_HISTORY_RUNS * 2 {
+        compact_history(history);
+    }
+}
+
+// Runs from earlier launches keep their IDs, so new runs carry on after them
+pub fn get_last_run_id() -> u64 {
+    let mut history = HISTORY.lock().unwrap();
+    history.get_or_insert_with(load_history).runs.iter().map(|record| record.id).max().unwrap_or(0)
+}
+
+// Newest runs come first
+pub fn get_run_history(macro_name: Option<&str>, limit: Option<usize>) -> Vec<RunRecord> {
+    let mut history = HISTORY.lock().unwrap();
+    history.get_or_insert_with(load_history).runs.iter().rev()
+        .filter(|record| macro_name.map_or(true, |macro_name| record.macro_name == macro_name))
+        .take(limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect()
+}
+
+pub fn clear_run_history() {
+    let mut history = HISTORY.lock().unwrap();
+    let mut cleared: History = History { path: get_history_path(), runs: Vec::new(), lines: 0 };
+    compact_history(&mut cleared);
+    *history = Some(cleared);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_path(name: &str) -> PathBuf {
+        let folder: PathBuf = std::env::temp_dir().join(format!("codemacros-history-test-{}", std::process::id()));
+        let _ = fs::create_dir_all(&folder);
+        let path: PathBuf = folder.join(format!("{}.jsonl", name));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(path.with_extension("json"));
+        path
+    }
+
+    fn run(id: u64) -> RunRecord {
+        RunRecord {
+            id,
+            macro_name: "History test".to_string(),
+            trigger: "manual".to_string(),
+            started: id,
+            ended: id + 1,
+            outcome: "succeeded".to_string(),
+            error: None,
+            trace: None,
+        }
+    }
+
+    fn get_ids(history: &History) -> Vec<u64> {
+        history.runs.iter().map(|record| record.id).collect()
+    }
+
+    #[test]
+    fn appends_runs_and_compacts_the_file() {
+        let path: PathBuf = get_test_path("compacts");
+        let mut history: History = load_history_file(path.clone());
+        for id in 1..MAX_HISTORY_RUNS as u64 * 2 {
+            add_run(&mut history, run(id));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), MAX_HISTORY_RUNS * 2 - 1);
+
+        add_run(&mut history, run(MAX_HISTORY_RUNS as u64 * 2));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), MAX_HISTORY_RUNS);
+
+        let loaded: History = load_history_file(path);
+        assert_eq!(loaded.lines, MAX_HISTORY_RUNS);
+        assert_eq!(get_ids(&loaded), get_ids(&history));
+        assert_eq!(loaded.runs.last().map(|record| record.id), Some(MAX_HISTORY_RUNS as u64 * 2));
+    }
+
+    #[test]
+    fn keeps_only_the_newest_runs_when_loading() {
+        let path: PathBuf = get_test_path("newest");
+        let runs: Vec<RunRecord> = (1..=MAX_HISTORY_RUNS as u64 + 5).map(run).collect();
+        write_history_file(&Some(path.clone()), &runs, false).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+
+        let history: History = load_history_file(path);
+        assert_eq!(history.runs.len(), MAX_HISTORY_RUNS);
+        assert_eq!(history.runs.first().map(|record| record.id), Some(6));
+    }
+
+    #[test]
+    fn migrates_the_old_history_file() {
+        let path: PathBuf = get_test_path("migrates");
+        fs::write(path.with_extension("json"), serde_json::to_string(&vec![run(1), run(2)]).unwrap()).unwrap();
+
+        let history: History = load_history_file(path.clone());
+        assert_eq!(get_ids(&history), vec![1, 2]);
+        assert!(!path.with_extension("json").exists());
+        assert_eq!(get_ids(&load_history_file(path)), vec![1, 2]);
+    }
+}
@@ -1,15 +1,31 @@
 pub mod keypress;
 pub mod cron;
+pub mod schedule;
+pub mod process;
+pub mod filewatch;
+pub mod webhook;
+pub mod lifecycle;
+pub mod clipboard;
+pub mod mouse;
+pub mod chain;
 
 use serde::{ Deserialize, Serialize };
 
-use super::execute::Execution;
+use super::execute::{ Execution, VariableType };
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Initiator {
     pub type_: String,
     pub data: InitiatorData,
     pub executes: Vec<Execution>,
+    pub variables: Option<Vec<VariableType>>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+// Macros and initiators saved before they could be disabled are enabled
+pub fn default_enabled() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -19,6 +35,25 @@ pub struct InitiatorData {
     pub time: Option<InitiatorKeypressTime>,
     pub cron: Option<String>,
     pub app_path: Option<String>,
+    pub app_event: Option<String>,
+    pub datetime: Option<String>,
+    pub interval: Option<f64>,
+    pub interval_unit: Option<String>,
+    pub jitter: Option<f64>,
+    pub weekdays: Option<Vec<String>>,
+    pub local_time: Option<String>,
+    pub run_missed: Option<bool>,
+    pub watch_path: Option<String>,
+    pub recursive: Option<bool>,
+    pub patterns: Option<Vec<String>>,
+    pub events: Option<Vec<String>>,
+    pub debounce: Option<f64>,
+    pub idle_minutes: Option<f64>,
+    pub idle_event: Option<String>,
+    pub session_event: Option<String>,
+    pub button: Option<String>,
+    pub macro_: Option<String>,
+    pub completed_event: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -0,0 +1,81 @@
+use super::super::{ get_macros, Macro };
+use super::super::execute::{ start_chained_macro_run, get_initiator_variables, is_initiator_active, VariableValue, MAX_CHAIN_DEPTH };
+
+// Runs "macro completed" initiators for a macro that just finished.
+// The depth is passed along so macros that run each other when they finish can't loop forever.
+pub fn run_initiator_macro_completed(macro_name: &str, success: bool, depth: u32) {
+    for (macro_, index) in get_completed_initiators(&get_macros(), macro_name, success, depth) {
+        let initiator = macro_.macro_.initiators.as_ref().unwrap()[index].clone();
+        let variables = get_initiator_variables(&initiator, vec![
+            ("macro", VariableValue::String(macro_name.to_string())),
+            ("success", VariableValue::Number(if success { 1.0 } else { 0.0 })),
+        ]);
+        start_chained_macro_run(initiator, index, macro_.clone(), variables, "macrocompleted".to_string(), depth + 1);
+    }
+}
+
+// Returns each macro with the index of an initiator that should run now that the macro finished
+fn get_completed_initiators<'a>(macros: &'a [Macro], macro_name: &str, success: bool, depth: u32) -> Vec<(&'a Macro, usize)> {
+    let mut completed: Vec<(&Macro, usize)> = vec![];
+    for macro_ in macros {
+        for (index, initiator) in macro_.macro_.initiators.as_ref().unwrap_or(&vec![]).iter().enumerate() {
+            if initiator.type_ != "macrocompleted" || initiator.data.macro_.as_deref() != Some(macro_name) || !is_initiator_active(initiator, macro_) {
+                continue;
+            }
+            let matches: bool = match initiator.data.completed_event.as_deref().unwrap_or("finished") {
+                "succeeded" => success,
+                "failed" => !success,
+                _ => true
+            };
+            if !matches {
+                continue;
+            }
+            if depth >= MAX_CHAIN_DEPTH {
+                println!("Not running macro \"{}\" because too many macros started each other", macro_.name);
+                continue;
+            }
+            completed.push((macro_, index));
+        }
+    }
+    completed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_macros() -> Vec<Macro> {
+        serde_json::from_value(serde_json::json!([
+            { "name": "After", "description": "", "macro_": { "initiators": [
+                { "type_": "macrocompleted", "data": { "macro_": "First" }, "executes": [], "variables": null },
+                { "type_": "macrocompleted", "data": { "macro_": "First", "completed_event": "succeeded" }, "executes": [], "variables": null },
+                { "type_": "macrocompleted", "data": { "macro_": "First", "completed_event": "failed" }, "executes": [], "variables": null },
+                { "type_": "macrocompleted", "data": { "macro_": "Other" }, "executes": [], "variables": null },
+                { "type_": "macrocompleted", "data": { "macro_": "First" }, "executes": [], "variables": null, "enabled": false }
+            ], "functions": null } },
+            { "name": "Disabled", "description": "", "enabled": false, "macro_": { "initiators": [
+                { "type_": "macrocompleted", "data": { "macro_": "First" }, "executes": [], "variables": null }
+            ], "functions": null } }
+        ])).unwrap()
+    }
+
+    fn get_indexes(macros: &[Macro], success: bool, depth: u32) -> Vec<(String, usize)> {
+        get_completed_initiators(macros, "First", success, depth).into_iter()
+            .map(|(macro_, index)| (macro_.name.clone(), index))
+            .collect()
+    }
+
+    #[test]
+    fn matches_how_the_macro_finished() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(get_indexes(&macros, true, 0), vec![("After".to_string(), 0), ("After".to_string(), 1)]);
+        assert_eq!(get_indexes(&macros, false, 0), vec![("After".to_string(), 0), ("After".to_string(), 2)]);
+    }
+
+    #[test]
+    fn limits_how_deep_macros_run_each_other() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(get_indexes(&macros, true, MAX_CHAIN_DEPTH - 1).len(), 2);
+        assert_eq!(get_indexes(&macros, true, MAX_CHAIN_DEPTH), vec![]);
+    }
+}
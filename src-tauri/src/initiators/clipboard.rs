@@ -0,0 +1,92 @@
+use super::super::get_macros;
+use super::super::execute::{ run_macro_initiator_with_variables, get_initiator_variables, VariableValue };
+use super::super::clipboard::{ lock_clipboard, Clipboard };
+
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Not every platform can notify us about clipboard changes, so the text is compared on an interval instead
+pub fn listen_initiator_clipboard() {
+    let mut previous_text: Option<String> = None;
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let has_initiators: bool = get_macros().iter().any(|macro_| {
+            macro_.macro_.initiators.as_ref().map_or(false, |initiators| initiators.iter().any(|initiator| initiator.type_ == "clipboard"))
+        });
+        if !has_initiators {
+            previous_text = None;
+            lock_clipboard().take_last_set_text();
+            continue;
+        }
+
+        let text: String = match get_clipboard_change(&mut lock_clipboard(), &mut previous_text) {
+            Some(text) => text,
+            None => continue
+        };
+
+        for macro_ in get_macros() {
+            for (index, initiator) in macro_.clone().macro_.initiators.unwrap_or(vec![]).into_iter().enumerate() {
+                if initiator.type_ != "clipboard" {
+                    continue;
+                }
+                let variables = get_initiator_variables(&initiator, vec![
+                    ("text", VariableValue::String(text.clone())),
+                ]);
+                run_macro_initiator_with_variables(initiator, index, macro_.clone(), variables);
+            }
+        }
+    }
+}
+
+// The new clipboard text if it changed since the last poll
+fn get_clipboard_change(clipboard: &mut Clipboard, previous_text: &mut Option<String>) -> Option<String> {
+    let text: Option<String> = clipboard.get_text();
+    let changed: bool = previous_text.is_some() && text.is_some() && text != *previous_text;
+    *previous_text = text.clone();
+    if !changed {
+        return None;
+    }
+
+    // Changes made by macros are skipped, otherwise a macro that edits the clipboard would keep running itself
+    if text == clipboard.take_last_set_text() {
+        return None;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::clipboard::{ MemoryClipboard, ClipboardBackend };
+
+    // Copies like the user would, without going through the clipboard's set_text
+    fn copy(clipboard: &mut Clipboard, text: &str) {
+        let mut backend: MemoryClipboard = MemoryClipboard::default();
+        backend.set_text(text.to_string()).unwrap();
+        clipboard.set_backend(Box::new(backend));
+    }
+
+    #[test]
+    fn skips_only_the_macros_own_change() {
+        let mut clipboard: Clipboard = Clipboard::new(Box::new(MemoryClipboard::default()));
+        let mut previous_text: Option<String> = None;
+        copy(&mut clipboard, "first");
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), None);
+
+        copy(&mut clipboard, "copied");
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), Some("copied".to_string()));
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), None);
+
+        clipboard.set_text("from a macro".to_string()).unwrap();
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), None);
+
+        copy(&mut clipboard, "something else");
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), Some("something else".to_string()));
+        copy(&mut clipboard, "from a macro");
+        assert_eq!(get_clipboard_change(&mut clipboard, &mut previous_text), Some("from a macro".to_string()));
+    }
+}
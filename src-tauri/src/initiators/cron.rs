@@ -1,7 +1,10 @@
 use super::super::get_macros;
-use super::super::execute::run_macro_initiator;
 use super::Initiator;
 use super::super::Macro;
+use super::schedule::{ get_last_run_key, get_last_runs, run_scheduled_initiator };
+
+use chrono::{ DateTime, Local, TimeZone };
+use std::collections::HashMap;
 
 use crony::{Job, Runner, Schedule};
 // use std::thread;
@@ -9,7 +12,9 @@ use derive_new::new;
 
 #[derive(new)]
 struct ExampleJob {
+    key: String,
     initiator: Initiator,
+    initiator_index: usize,
     macro_: Macro
 }
 
@@ -18,13 +23,13 @@ impl Job for ExampleJob {
         self.initiator.data.cron.as_ref().unwrap().parse().unwrap()
     }
     fn handle(&self) {
-        run_macro_initiator(self.initiator.clone(), self.macro_.clone())
+        run_scheduled_initiator(&self.key, &self.initiator, self.initiator_index, &self.macro_)
     }
 }
 
 static mut RUNNER: Option<Runner> = None;
 
-pub fn listen_initiator_cron() {
+pub fn listen_initiator_cron(check_missed: bool) {
     unsafe {
         match &RUNNER {
             Some(_) => {
@@ -38,12 +43,32 @@ pub fn listen_initiator_cron() {
     
     let mut runner: Runner = Runner::new();
 
+    let last_runs: HashMap<String, i64> = get_last_runs();
+
     for macro_ in macros {
         let initiators: Vec<Initiator> =  macro_.clone().macro_.initiators.unwrap_or(vec![]);
-        for initiator in initiators {
+        for (index, initiator) in initiators.into_iter().enumerate() {
             if initiator.type_ == "time" {
+                let key: String = get_last_run_key(&macro_, &initiator);
+
+                if check_missed && initiator.data.run_missed.unwrap_or(false) {
+                    let last_run: Option<DateTime<Local>> = last_runs.get(&key).and_then(|time| Local.timestamp_opt(*time, 0).single());
+                    if let Some(last_run) = last_run {
+                        let schedule: Option<Schedule> = initiator.data.cron.as_ref().and_then(|cron| cron.parse().ok());
+                        let missed: bool = schedule
+                            .and_then(|schedule| schedule.after(&last_run).next())
+                            .map_or(false, |next| next <= Local::now());
+                        if missed {
+                            println!("Running missed occurrence of macro \"{}\"", macro_.name);
+                            run_scheduled_initiator(&key, &initiator, index, &macro_);
+                        }
+                    }
+                }
+
                 runner = runner.add(Box::new(ExampleJob::new(
+                    key,
                     initiator.clone(),
+                    index,
                     macro_.clone()
                 )));
             }
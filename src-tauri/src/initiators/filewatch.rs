@@ -0,0 +1,175 @@
+use super::super::get_macros;
+use super::super::execute::{ run_macro_initiator_with_variables, get_initiator_variables, VariableValue };
+use super::Initiator;
+use super::super::Macro;
+
+use notify::{ Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher };
+use notify::event::{ ModifyKind, RenameMode };
+use glob::Pattern;
+use once_cell::sync::Lazy;
+
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use std::sync::mpsc::{ channel, Receiver, RecvTimeoutError };
+use std::thread;
+use std::time::{ Duration, Instant };
+
+// Dropping a watcher stops it, which also disconnects its channel and ends its debounce thread
+static WATCHERS: Lazy<Mutex<Vec<RecommendedWatcher>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+const DEFAULT_DEBOUNCE: f64 = 500.0;
+
+#[derive(Clone, Debug)]
+struct FileEvent {
+    kind: String,
+    path: PathBuf,
+    old_path: Option<PathBuf>,
+}
+
+pub fn listen_initiator_filewatch() {
+    let mut watchers = WATCHERS.lock().unwrap();
+    watchers.clear();
+
+    for macro_ in get_macros() {
+        let initiators: Vec<Initiator> = macro_.clone().macro_.initiators.unwrap_or(vec![]);
+        for (index, initiator) in initiators.into_iter().enumerate() {
+            if initiator.type_ != "filewatch" {
+                continue;
+            }
+            let watch_path: PathBuf = match &initiator.data.watch_path {
+                Some(watch_path) if !watch_path.trim().is_empty() => PathBuf::from(watch_path.trim()),
+                _ => continue
+            };
+            // Events have absolute paths, so the watched folder needs one too for patterns to match paths inside it
+            let watch_path: PathBuf = watch_path.canonicalize().unwrap_or(watch_path);
+
+            let (sender, receiver) = channel::<Event>();
+            let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+                if let Ok(event) = result {
+                    let _ = sender.send(event);
+                }
+            });
+            let mut watcher: RecommendedWatcher = match watcher {
+                Ok(watcher) => watcher,
+                Err(error) => {
+                    println!("Failed to create a file watcher for macro \"{}\": {}", macro_.name, error);
+                    continue;
+                }
+            };
+
+            let recursive_mode: RecursiveMode = if initiator.data.recursive.unwrap_or(true) {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            if let Err(error) = watcher.watch(&watch_path, recursive_mode) {
+                println!("Failed to watch \"{}\" for macro \"{}\": {}", watch_path.display(), macro_.name, error);
+                continue;
+            }
+
+            let macro_ = macro_.clone();
+            thread::spawn(move || {
+                debounce_file_events(receiver, initiator, index, macro_, watch_path);
+            });
+
+            watchers.push(watcher);
+        }
+    }
+}
+
+// Editors and downloads usually produce a burst of events for one file, so wait until a path has been quiet
+// for the debounce time before running the initiator once for it
+fn debounce_file_events(receiver: Receiver<Event>, initiator: Initiator, initiator_index: usize, macro_: Macro, watch_path: PathBuf) {
+    let debounce: Duration = Duration::from_millis(initiator.data.debounce.unwrap_or(DEFAULT_DEBOUNCE).max(0.0) as u64);
+    let patterns: Vec<Pattern> = initiator.data.patterns.clone().unwrap_or(vec![]).iter()
+        .filter(|pattern| !pattern.trim().is_empty())
+        .filter_map(|pattern| Pattern::new(pattern.trim()).ok())
+        .collect();
+
+    let mut pending: HashMap<PathBuf, (FileEvent, Instant)> = HashMap::new();
+
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(50)) {
+            Ok(event) => {
+                if let Some(file_event) = get_file_event(event) {
+                    let merged: FileEvent = match pending.get(&file_event.path) {
+                        // A file that was just created is still "created" after being written to
+                        Some((previous, _)) if previous.kind == "create" && file_event.kind == "modify" => previous.clone(),
+                        _ => file_event
+                    };
+                    pending.insert(merged.path.clone(), (merged, Instant::now()));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return
+        }
+
+        let ready: Vec<PathBuf> = pending.iter()
+            .filter(|(_, (_, time))| time.elapsed() >= debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in ready {
+            let (file_event, _) = pending.remove(&path).unwrap();
+            if event_matches(&initiator, &patterns, &watch_path, &file_event) {
+                let variables = get_initiator_variables(&initiator, vec![
+                    ("path", VariableValue::String(file_event.path.to_string_lossy().to_string())),
+                    ("kind", VariableValue::String(file_event.kind.clone())),
+                    ("oldpath", VariableValue::String(
+                        file_event.old_path.as_ref().map(|path| path.to_string_lossy().to_string()).unwrap_or_default()
+                    )),
+                ]);
+                run_macro_initiator_with_variables(initiator.clone(), initiator_index, macro_.clone(), variables);
+            }
+        }
+    }
+}
+
+fn get_file_event(event: Event) -> Option<FileEvent> {
+    let kind: &str = match event.kind {
+        EventKind::Create(_) => "create",
+        EventKind::Remove(_) => "delete",
+        EventKind::Modify(ModifyKind::Name(_)) => "rename",
+        EventKind::Modify(_) => "modify",
+        _ => return None
+    };
+
+    // Renames with both paths are reported as (from, to)
+    if let EventKind::Modify(ModifyKind::Name(RenameMode::Both)) = event.kind {
+        if event.paths.len() >= 2 {
+            return Some(FileEvent {
+                kind: kind.to_string(),
+                path: event.paths[1].clone(),
+                old_path: Some(event.paths[0].clone()),
+            });
+        }
+    }
+
+    Some(FileEvent {
+        kind: kind.to_string(),
+        path: event.paths.first()?.clone(),
+        old_path: None,
+    })
+}
+
+fn event_matches(initiator: &Initiator, patterns: &[Pattern], watch_path: &Path, file_event: &FileEvent) -> bool {
+    if let Some(events) = &initiator.data.events {
+        if !events.is_empty() && !events.contains(&file_event.kind) {
+            return false;
+        }
+    }
+
+    if patterns.is_empty() {
+        return true;
+    }
+
+    // Patterns can match either the file name ("*.png") or the path inside the watched folder ("screenshots/*.png")
+    let file_name: Option<&str> = file_event.path.file_name().and_then(|name| name.to_str());
+    let relative_path: Option<&Path> = file_event.path.strip_prefix(watch_path).ok();
+
+    patterns.iter().any(|pattern| {
+        file_name.map_or(false, |file_name| pattern.matches(file_name))
+            || relative_path.map_or(false, |relative_path| pattern.matches_path(relative_path))
+    })
+}
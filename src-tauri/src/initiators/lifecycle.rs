@@ -0,0 +1,554 @@
+use super::super::get_macros;
+use super::super::execute::{ run_macro_initiator, run_macro_initiator_with_variables, get_initiator_variables, VariableValue };
+use super::Initiator;
+
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+const DEFAULT_IDLE_MINUTES: f64 = 5.0;
+
+// Set once the app has started, and cleared when the startup initiators run with the first macros the frontend sends
+static STARTUP_PENDING: AtomicBool = AtomicBool::new(false);
+
+// Where idle and lock state comes from. These are traits so other sources, like synthetic ones, can be plugged in.
+pub trait IdleSource: Send {
+    // The time since the user last used the keyboard or mouse, or None if it can't be detected
+    fn get_idle_time(&mut self) -> Option<Duration>;
+}
+
+pub trait LockSource: Send {
+    // Whether the session is locked, or None if it can't be detected
+    fn is_locked(&mut self) -> Option<bool>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifecycleEvent {
+    IdleTime { previous: Duration, current: Duration },
+    Lock,
+    Unlock,
+}
+
+// Turns the state reported by the sources into events by comparing it to the last poll
+pub struct LifecycleWatcher {
+    idle_source: Box<dyn IdleSource>,
+    lock_source: Box<dyn LockSource>,
+    previous_idle_time: Option<Duration>,
+    previous_locked: Option<bool>,
+}
+
+impl LifecycleWatcher {
+    pub fn new(idle_source: Box<dyn IdleSource>, lock_source: Box<dyn LockSource>) -> LifecycleWatcher {
+        LifecycleWatcher {
+            idle_source,
+            lock_source,
+            previous_idle_time: None,
+            previous_locked: None,
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<LifecycleEvent> {
+        let mut events: Vec<LifecycleEvent> = Vec::new();
+
+        let idle_time: Option<Duration> = self.idle_source.get_idle_time();
+        if let (Some(previous), Some(current)) = (self.previous_idle_time, idle_time) {
+            events.push(LifecycleEvent::IdleTime { previous, current });
+        }
+        self.previous_idle_time = idle_time;
+
+        let locked: Option<bool> = self.lock_source.is_locked();
+        match (self.previous_locked, locked) {
+            (Some(false), Some(true)) => events.push(LifecycleEvent::Lock),
+            (Some(true), Some(false)) => events.push(LifecycleEvent::Unlock),
+            _ => {}
+        }
+        self.previous_locked = locked;
+
+        events
+    }
+
+    // Forgets the last state, so nothing fires for changes that happened while the sources weren't polled
+    pub fn reset(&mut self) {
+        self.previous_idle_time = None;
+        self.previous_locked = None;
+    }
+}
+
+pub fn mark_app_started() {
+    STARTUP_PENDING.store(true, Ordering::SeqCst);
+}
+
+// Macros are only sent by the frontend after the app has started, so "startup" initiators run the first time they arrive
+pub fn listen_initiator_startup() {
+    if !STARTUP_PENDING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    for macro_ in get_macros() {
+        for (index, initiator) in macro_.clone().macro_.initiators.unwrap_or(vec![]).into_iter().enumerate() {
+            if initiator.type_ == "startup" {
+                run_macro_initiator(initiator, index, macro_.clone());
+            }
+        }
+    }
+}
+
+pub fn listen_initiator_lifecycle() {
+    let mut watcher: LifecycleWatcher = LifecycleWatcher::new(get_idle_source(), get_lock_source());
+
+    loop {
+        if has_lifecycle_initiators() {
+            for event in watcher.poll() {
+                handle_lifecycle_event(event);
+            }
+        } else {
+            watcher.reset();
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub fn handle_lifecycle_event(event: LifecycleEvent) {
+    for macro_ in get_macros() {
+        for (index, initiator) in macro_.clone().macro_.initiators.unwrap_or(vec![]).into_iter().enumerate() {
+            match (initiator.type_.as_str(), event) {
+                ("idle", LifecycleEvent::IdleTime { previous, current }) => {
+                    if let Some(away_time) = get_idle_away_time(&initiator, previous, current) {
+                        let variables = get_initiator_variables(&initiator, vec![
+                            ("idletime", VariableValue::Number(away_time.as_secs_f64())),
+                        ]);
+                        run_macro_initiator_with_variables(initiator.clone(), index, macro_.clone(), variables);
+                    }
+                }
+                ("session", LifecycleEvent::Lock) if initiator.data.session_event.as_deref().unwrap_or("lock") == "lock" => {
+                    run_macro_initiator(initiator, index, macro_.clone());
+                }
+                ("session", LifecycleEvent::Unlock) if initiator.data.session_event.as_deref() == Some("unlock") => {
+                    run_macro_initiator(initiator, index, macro_.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Returns how long the user has been away if an idle initiator should run for this change in idle time
+fn get_idle_away_time(initiator: &Initiator, previous: Duration, current: Duration) -> Option<Duration> {
+    let idle_time: Duration = Duration::from_secs_f64(
+        initiator.data.idle_minutes.unwrap_or(DEFAULT_IDLE_MINUTES).max(0.0) * 60.0
+    );
+    // Returning from idle uses how long the user was away, since the idle time has just reset
+    match initiator.data.idle_event.as_deref().unwrap_or("idle") {
+        "idle" if previous < idle_time && current >= idle_time => Some(current),
+        "return" if previous >= idle_time && current < previous => Some(previous),
+        _ => None
+    }
+}
+
+fn initiator_is_lifecycle(initiator: &Initiator) -> bool {
+    initiator.type_ == "idle" || initiator.type_ == "session"
+}
+
+// The sources aren't polled when no macro needs them
+fn has_lifecycle_initiators() -> bool {
+    get_macros().iter().any(|macro_| {
+        macro_.macro_.initiators.as_ref().map_or(false, |initiators| initiators.iter().any(initiator_is_lifecycle))
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+struct UnsupportedSource;
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+impl IdleSource for UnsupportedSource {
+    fn get_idle_time(&mut self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+impl LockSource for UnsupportedSource {
+    fn is_locked(&mut self) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_idle_source() -> Box<dyn IdleSource> {
+    // XWayland only sees input that goes to X11 windows, so only trust X11 outside of Wayland sessions
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        if let Some(source) = linux::X11IdleSource::new() {
+            return Box::new(source);
+        }
+    }
+    Box::new(linux::LogindSource::new())
+}
+
+#[cfg(target_os = "linux")]
+fn get_lock_source() -> Box<dyn LockSource> {
+    Box::new(linux::LogindSource::new())
+}
+
+#[cfg(target_os = "windows")]
+fn get_idle_source() -> Box<dyn IdleSource> {
+    Box::new(windows::WindowsSource)
+}
+
+#[cfg(target_os = "windows")]
+fn get_lock_source() -> Box<dyn LockSource> {
+    Box::new(windows::WindowsSource)
+}
+
+#[cfg(target_os = "macos")]
+fn get_idle_source() -> Box<dyn IdleSource> {
+    Box::new(macos::IoregIdleSource)
+}
+
+#[cfg(target_os = "macos")]
+fn get_lock_source() -> Box<dyn LockSource> {
+    Box::new(UnsupportedSource)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn get_idle_source() -> Box<dyn IdleSource> {
+    Box::new(UnsupportedSource)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn get_lock_source() -> Box<dyn LockSource> {
+    Box::new(UnsupportedSource)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{ IdleSource, LockSource };
+
+    use std::ptr;
+    use std::sync::{ Mutex, Once };
+    use std::thread;
+    use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+    use once_cell::sync::Lazy;
+
+    use dbus::arg::prop_cast;
+    use dbus::blocking::Connection;
+    use dbus::blocking::stdintf::org_freedesktop_dbus::{ Properties, PropertiesPropertiesChanged };
+
+    use x11_dl::xlib::{ Display, Xlib };
+    use x11_dl::xss::{ XScreenSaverInfo, Xss };
+
+    // Asks the X server how long it's been since the last input. The libraries are loaded at runtime,
+    // so this just isn't used if they aren't installed.
+    pub struct X11IdleSource {
+        xlib: Xlib,
+        xss: Xss,
+        display: *mut Display,
+        info: *mut XScreenSaverInfo,
+    }
+
+    // The display connection is only ever used from the thread that polls it
+    unsafe impl Send for X11IdleSource {}
+
+    impl X11IdleSource {
+        pub fn new() -> Option<X11IdleSource> {
+            let xlib: Xlib = Xlib::open().ok()?;
+            let xss: Xss = Xss::open().ok()?;
+            unsafe {
+                let display: *mut Display = (xlib.XOpenDisplay)(ptr::null());
+                if display.is_null() {
+                    return None;
+                }
+                let info: *mut XScreenSaverInfo = (xss.XScreenSaverAllocInfo)();
+                if info.is_null() {
+                    (xlib.XCloseDisplay)(display);
+                    return None;
+                }
+                Some(X11IdleSource { xlib, xss, display, info })
+            }
+        }
+    }
+
+    impl IdleSource for X11IdleSource {
+        fn get_idle_time(&mut self) -> Option<Duration> {
+            unsafe {
+                let root = (self.xlib.XDefaultRootWindow)(self.display);
+                if (self.xss.XScreenSaverQueryInfo)(self.display, root, self.info) == 0 {
+                    return None;
+                }
+                Some(Duration::from_secs_f64((*self.info).idle as f64 / 1000.0))
+            }
+        }
+    }
+
+    impl Drop for X11IdleSource {
+        fn drop(&mut self) {
+            unsafe {
+                (self.xlib.XFree)(self.info as *mut _);
+                (self.xlib.XCloseDisplay)(self.display);
+            }
+        }
+    }
+
+    // Reads the session's hints from logind, which desktop environments keep up to date on Wayland too.
+    // The idle hint is only set after the desktop's own idle delay.
+    pub struct LogindSource;
+
+    #[derive(Clone, Copy, Default)]
+    struct SessionHints {
+        idle: bool,
+        // Microseconds since the unix epoch
+        idle_since: u64,
+        locked: bool,
+    }
+
+    // Filled in by a thread listening to logind over D-Bus, and None while it isn't connected
+    static SESSION_HINTS: Lazy<Mutex<Option<SessionHints>>> = Lazy::new(|| Mutex::new(None));
+    static LISTEN_SESSION_HINTS: Once = Once::new();
+
+    const LOGIND_NAME: &str = "org.freedesktop.login1";
+    const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+    const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+    impl LogindSource {
+        // Both sources share one listener, which is started by whichever is made first
+        pub fn new() -> LogindSource {
+            LISTEN_SESSION_HINTS.call_once(|| {
+                thread::spawn(|| {
+                    if let Err(error) = listen_session_hints() {
+                        println!("Failed to listen to logind: {}", error);
+                    }
+                    *SESSION_HINTS.lock().unwrap_or_else(|error| error.into_inner()) = None;
+                });
+            });
+            LogindSource
+        }
+    }
+
+    fn get_session_hints() -> Option<SessionHints> {
+        *SESSION_HINTS.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn listen_session_hints() -> Result<(), dbus::Error> {
+        let connection: Connection = Connection::new_system()?;
+        let manager = connection.with_proxy(LOGIND_NAME, "/org/freedesktop/login1", DBUS_TIMEOUT);
+        let session_id: String = std::env::var("XDG_SESSION_ID").unwrap_or("auto".to_string());
+        let (session_path,): (dbus::Path<'static>,) = manager.method_call("org.freedesktop.login1.Manager", "GetSession", (session_id,))?;
+        let session = connection.with_proxy(LOGIND_NAME, session_path, DBUS_TIMEOUT);
+
+        // logind sends the new values along with the change, so the properties are only read once
+        session.match_signal(|signal: PropertiesPropertiesChanged, _: &Connection, _: &dbus::Message| {
+            if signal.interface_name == SESSION_INTERFACE {
+                let mut hints = SESSION_HINTS.lock().unwrap_or_else(|error| error.into_inner());
+                if let Some(hints) = hints.as_mut() {
+                    if let Some(idle) = prop_cast::<bool>(&signal.changed_properties, "IdleHint") {
+                        hints.idle = *idle;
+                    }
+                    if let Some(idle_since) = prop_cast::<u64>(&signal.changed_properties, "IdleSinceHint") {
+                        hints.idle_since = *idle_since;
+                    }
+                    if let Some(locked) = prop_cast::<bool>(&signal.changed_properties, "LockedHint") {
+                        hints.locked = *locked;
+                    }
+                }
+            }
+            true
+        })?;
+
+        let hints: SessionHints = SessionHints {
+            idle: session.get(SESSION_INTERFACE, "IdleHint")?,
+            idle_since: session.get(SESSION_INTERFACE, "IdleSinceHint")?,
+            locked: session.get(SESSION_INTERFACE, "LockedHint")?,
+        };
+        *SESSION_HINTS.lock().unwrap_or_else(|error| error.into_inner()) = Some(hints);
+
+        loop {
+            connection.process(Duration::from_secs(60))?;
+        }
+    }
+
+    impl IdleSource for LogindSource {
+        fn get_idle_time(&mut self) -> Option<Duration> {
+            let hints: SessionHints = get_session_hints()?;
+            if !hints.idle {
+                return Some(Duration::ZERO);
+            }
+            let now: Duration = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Some(now.saturating_sub(Duration::from_micros(hints.idle_since)))
+        }
+    }
+
+    impl LockSource for LogindSource {
+        fn is_locked(&mut self) -> Option<bool> {
+            get_session_hints().map(|hints| hints.locked)
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::{ IdleSource, LockSource };
+
+    use std::mem;
+    use std::time::Duration;
+
+    use winapi::um::sysinfoapi::GetTickCount;
+    use winapi::um::winuser::{ CloseDesktop, GetLastInputInfo, GetUserObjectInformationW, OpenInputDesktop, DESKTOP_READOBJECTS, LASTINPUTINFO, UOI_NAME };
+
+    pub struct WindowsSource;
+
+    impl IdleSource for WindowsSource {
+        fn get_idle_time(&mut self) -> Option<Duration> {
+            unsafe {
+                let mut last_input: LASTINPUTINFO = mem::zeroed();
+                last_input.cbSize = mem::size_of::<LASTINPUTINFO>() as u32;
+                if GetLastInputInfo(&mut last_input) == 0 {
+                    return None;
+                }
+                // Both are milliseconds since boot that wrap around after 49 days
+                Some(Duration::from_millis(GetTickCount().wrapping_sub(last_input.dwTime) as u64))
+            }
+        }
+    }
+
+    impl LockSource for WindowsSource {
+        // While the lock screen is showing, the input desktop is the secure "Winlogon" one, which can't be opened
+        // or is named something other than "Default"
+        fn is_locked(&mut self) -> Option<bool> {
+            unsafe {
+                let desktop = OpenInputDesktop(0, 0, DESKTOP_READOBJECTS);
+                if desktop.is_null() {
+                    return Some(true);
+                }
+                let mut name: [u16; 64] = [0; 64];
+                let mut length: u32 = 0;
+                let found: bool = GetUserObjectInformationW(
+                    desktop as *mut _, UOI_NAME as i32, name.as_mut_ptr() as *mut _, (name.len() * 2) as u32, &mut length
+                ) != 0;
+                CloseDesktop(desktop);
+                if !found {
+                    return None;
+                }
+                let name: String = String::from_utf16_lossy(&name[..name.iter().position(|&c| c == 0).unwrap_or(name.len())]);
+                Some(!name.eq_ignore_ascii_case("Default"))
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::IdleSource;
+
+    use std::process::Command;
+    use std::time::Duration;
+
+    pub struct IoregIdleSource;
+
+    impl IdleSource for IoregIdleSource {
+        fn get_idle_time(&mut self) -> Option<Duration> {
+            let output = Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().ok()?;
+            // Looks like: "HIDIdleTime" = 1234567890 (nanoseconds)
+            let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+            let line: &str = stdout.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+            let nanoseconds: u64 = line.split('=').nth(1)?.trim().parse().ok()?;
+            Some(Duration::from_nanos(nanoseconds))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    // Reports the given values in order, then can't detect anything
+    struct FakeIdleSource(VecDeque<Option<Duration>>);
+    struct FakeLockSource(VecDeque<Option<bool>>);
+
+    impl IdleSource for FakeIdleSource {
+        fn get_idle_time(&mut self) -> Option<Duration> {
+            self.0.pop_front().flatten()
+        }
+    }
+
+    impl LockSource for FakeLockSource {
+        fn is_locked(&mut self) -> Option<bool> {
+            self.0.pop_front().flatten()
+        }
+    }
+
+    fn get_watcher(idle_times: Vec<Option<u64>>, locked: Vec<Option<bool>>) -> LifecycleWatcher {
+        LifecycleWatcher::new(
+            Box::new(FakeIdleSource(idle_times.into_iter().map(|time| time.map(Duration::from_secs)).collect())),
+            Box::new(FakeLockSource(locked.into_iter().collect())),
+        )
+    }
+
+    fn idle(previous: u64, current: u64) -> LifecycleEvent {
+        LifecycleEvent::IdleTime { previous: Duration::from_secs(previous), current: Duration::from_secs(current) }
+    }
+
+    #[test]
+    fn reports_idle_time_changes() {
+        let mut watcher: LifecycleWatcher = get_watcher(vec![Some(1), Some(2), None, Some(0), Some(5)], vec![]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![idle(1, 2)]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![idle(0, 5)]);
+    }
+
+    #[test]
+    fn reports_lock_and_unlock() {
+        let mut watcher: LifecycleWatcher = get_watcher(vec![], vec![Some(false), Some(true), Some(true), Some(false), None, Some(true)]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![LifecycleEvent::Lock]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![LifecycleEvent::Unlock]);
+        assert_eq!(watcher.poll(), vec![]);
+        assert_eq!(watcher.poll(), vec![]);
+    }
+
+    fn get_idle_initiator(data: serde_json::Value) -> Initiator {
+        serde_json::from_value(serde_json::json!({ "type_": "idle", "data": data, "executes": [], "variables": null })).unwrap()
+    }
+
+    fn get_away_time(initiator: &Initiator, previous: u64, current: u64) -> Option<u64> {
+        get_idle_away_time(initiator, Duration::from_secs(previous), Duration::from_secs(current)).map(|time| time.as_secs())
+    }
+
+    #[test]
+    fn runs_when_crossing_the_idle_time() {
+        let initiator: Initiator = get_idle_initiator(serde_json::json!({ "idle_minutes": 2.0 }));
+        assert_eq!(get_away_time(&initiator, 119, 120), Some(120));
+        assert_eq!(get_away_time(&initiator, 100, 130), Some(130));
+        assert_eq!(get_away_time(&initiator, 100, 119), None);
+        assert_eq!(get_away_time(&initiator, 120, 121), None);
+        assert_eq!(get_away_time(&initiator, 130, 0), None);
+
+        // Five minutes by default
+        let initiator: Initiator = get_idle_initiator(serde_json::json!({}));
+        assert_eq!(get_away_time(&initiator, 299, 300), Some(300));
+        assert_eq!(get_away_time(&initiator, 119, 120), None);
+    }
+
+    #[test]
+    fn runs_when_returning_after_the_idle_time() {
+        let initiator: Initiator = get_idle_initiator(serde_json::json!({ "idle_minutes": 2.0, "idle_event": "return" }));
+        assert_eq!(get_away_time(&initiator, 150, 0), Some(150));
+        assert_eq!(get_away_time(&initiator, 120, 1), Some(120));
+        assert_eq!(get_away_time(&initiator, 119, 0), None);
+        assert_eq!(get_away_time(&initiator, 150, 151), None);
+        assert_eq!(get_away_time(&initiator, 119, 120), None);
+    }
+
+    #[test]
+    fn ignores_changes_from_before_a_reset() {
+        let mut watcher: LifecycleWatcher = get_watcher(vec![Some(1), Some(600)], vec![Some(false), Some(true)]);
+        assert_eq!(watcher.poll(), vec![]);
+        watcher.reset();
+        assert_eq!(watcher.poll(), vec![]);
+    }
+}
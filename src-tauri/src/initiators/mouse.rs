@@ -0,0 +1,152 @@
+use inputbot::MouseButton;
+
+use std::thread;
+
+use super::super::{ get_macros, Macro };
+use super::super::execute::{ run_macro_initiator_with_variables, get_initiator_variables, VariableValue };
+use super::keypress::get_pressed_keys;
+use super::super::keys::get_button_name;
+use super::super::settings::uses_uinput;
+
+// Binds must be set before inputbot starts handling input events, so call this before listen_initiator_keypress
+pub fn listen_initiator_mouse() {
+    MouseButton::bind_all(move |event| {
+        // The evdev listener handles mouse input instead
+        if !uses_uinput() {
+            run_mouse_button_initiators(event);
+        }
+    });
+
+    // inputbot doesn't report the scroll wheel, so it's listened to separately
+    thread::spawn(move || {
+        let result = rdev::listen(|event| {
+            if let rdev::EventType::Wheel { delta_x, delta_y } = event.event_type {
+                if !uses_uinput() {
+                    run_scroll_initiators(delta_x, delta_y);
+                }
+            }
+        });
+        if let Err(error) = result {
+            println!("Failed to listen for scroll events: {:?}", error);
+        }
+    });
+}
+
+pub fn run_mouse_button_initiators(button: MouseButton) {
+    if let Some(button) = js_button(button) {
+        run_mouse_initiators(&button, 0.0);
+    }
+}
+
+pub fn run_scroll_initiators(delta_x: i64, delta_y: i64) {
+    if let Some((button, delta)) = get_scroll_button(delta_x, delta_y) {
+        run_mouse_initiators(button, delta as f64);
+    }
+}
+
+// Positive deltas are right or up
+fn get_scroll_button(delta_x: i64, delta_y: i64) -> Option<(&'static str, i64)> {
+    match (delta_x, delta_y) {
+        (_, delta_y) if delta_y > 0 => Some(("scrollup", delta_y)),
+        (_, delta_y) if delta_y < 0 => Some(("scrolldown", -delta_y)),
+        (delta_x, _) if delta_x > 0 => Some(("scrollright", delta_x)),
+        (delta_x, _) if delta_x < 0 => Some(("scrollleft", -delta_x)),
+        _ => None
+    }
+}
+
+fn run_mouse_initiators(button: &str, delta: f64) {
+    let keys_pressed: Vec<String> = get_pressed_keys();
+
+    for (macro_, index) in get_matching_initiators(&get_macros(), button, &keys_pressed) {
+        let initiator = &macro_.macro_.initiators.as_ref().unwrap()[index];
+        let variables = get_initiator_variables(initiator, vec![
+            ("button", VariableValue::String(button.to_string())),
+            ("delta", VariableValue::Number(delta)),
+        ]);
+        run_macro_initiator_with_variables(initiator.clone(), index, macro_.clone(), variables);
+    }
+}
+
+// Returns each macro with the index of a mouse initiator for this button
+fn get_matching_initiators<'a>(macros: &'a [Macro], button: &str, keys_pressed: &[String]) -> Vec<(&'a Macro, usize)> {
+    let mut matching: Vec<(&Macro, usize)> = vec![];
+    for macro_ in macros {
+        let initiators = match macro_.macro_.initiators.as_ref() {
+            Some(initiators) => initiators,
+            None => continue
+        };
+        // Like keypress initiators, any other keys can be held as well
+        'initiators: for (index, initiator) in initiators.iter().enumerate() {
+            if initiator.type_ != "mouse" || initiator.data.button.as_deref() != Some(button) {
+                continue;
+            }
+            for key in initiator.data.keys.as_ref().unwrap_or(&vec![]) {
+                if !keys_pressed.contains(key) {
+                    continue 'initiators;
+                }
+            }
+            matching.push((macro_, index));
+        }
+    }
+    matching
+}
+
+// Turn the button enum into the same format as comes from the macro config
+fn js_button(button: MouseButton) -> Option<String> {
+    get_button_name(button).map(|name| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_macros() -> Vec<Macro> {
+        serde_json::from_value(serde_json::json!([
+            { "name": "Clicks", "description": "", "macro_": { "initiators": [
+                { "type_": "mouse", "data": { "button": "left" }, "executes": [], "variables": null },
+                { "type_": "keypress", "data": { "keys": ["left"] }, "executes": [], "variables": null },
+                { "type_": "mouse", "data": { "button": "left", "keys": ["ctrl", "shift"] }, "executes": [], "variables": null }
+            ], "functions": null } },
+            { "name": "Scrolls", "description": "", "macro_": { "initiators": [
+                { "type_": "mouse", "data": { "button": "scrollup", "keys": ["alt"] }, "executes": [], "variables": null }
+            ], "functions": null } }
+        ])).unwrap()
+    }
+
+    fn get_names(macros: &[Macro], button: &str, keys_pressed: &[&str]) -> Vec<(String, usize)> {
+        let keys_pressed: Vec<String> = keys_pressed.iter().map(|key| key.to_string()).collect();
+        get_matching_initiators(macros, button, &keys_pressed).into_iter()
+            .map(|(macro_, index)| (macro_.name.clone(), index))
+            .collect()
+    }
+
+    #[test]
+    fn matches_buttons() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(get_names(&macros, "left", &[]), vec![("Clicks".to_string(), 0)]);
+        assert_eq!(get_names(&macros, "right", &[]), vec![]);
+    }
+
+    #[test]
+    fn matches_held_keys() {
+        let macros: Vec<Macro> = get_test_macros();
+        assert_eq!(get_names(&macros, "left", &["ctrl"]), vec![("Clicks".to_string(), 0)]);
+        assert_eq!(
+            get_names(&macros, "left", &["shift", "ctrl", "a"]),
+            vec![("Clicks".to_string(), 0), ("Clicks".to_string(), 2)]
+        );
+        assert_eq!(get_names(&macros, "scrollup", &[]), vec![]);
+        assert_eq!(get_names(&macros, "scrollup", &["alt"]), vec![("Scrolls".to_string(), 0)]);
+    }
+
+    #[test]
+    fn gets_scroll_directions() {
+        assert_eq!(get_scroll_button(0, 2), Some(("scrollup", 2)));
+        assert_eq!(get_scroll_button(0, -1), Some(("scrolldown", 1)));
+        assert_eq!(get_scroll_button(3, 0), Some(("scrollright", 3)));
+        assert_eq!(get_scroll_button(-1, 0), Some(("scrollleft", 1)));
+        assert_eq!(get_scroll_button(1, -1), Some(("scrolldown", 1)));
+        assert_eq!(get_scroll_button(0, 0), None);
+    }
+}
@@ -0,0 +1,222 @@
+use super::super::get_macros;
+use super::super::execute::{ run_macro_initiator_with_variables, get_initiator_variables, VariableValue };
+use super::Initiator;
+
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::thread;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use std::fs;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Clone, Debug)]
+struct ProcessInfo {
+    pid: u32,
+    name: String,
+    path: Option<PathBuf>,
+}
+
+// Fires "application" initiators when a matching program starts or exits.
+// Programs like browsers start many processes, so an initiator tracks whether *any* matching process is running
+// and only fires when that changes.
+pub fn listen_initiator_process() {
+    // Matching processes for each initiator from the last poll, keyed by macro name, initiator index and app path
+    let mut previous: HashMap<String, Vec<ProcessInfo>> = HashMap::new();
+
+    loop {
+        let processes: Vec<ProcessInfo> = get_processes();
+        let mut current: HashMap<String, Vec<ProcessInfo>> = HashMap::new();
+
+        for macro_ in get_macros() {
+            let initiators: Vec<Initiator> = macro_.clone().macro_.initiators.unwrap_or(vec![]);
+            for (index, initiator) in initiators.into_iter().enumerate() {
+                if initiator.type_ != "application" {
+                    continue;
+                }
+                let app_path: String = match &initiator.data.app_path {
+                    Some(app_path) if !app_path.trim().is_empty() => app_path.trim().to_string(),
+                    _ => continue
+                };
+
+                let key: String = format!("{}#{}#{}", macro_.name, index, app_path);
+                let matcher: AppMatcher = get_app_matcher(&app_path);
+                let matching: Vec<ProcessInfo> = processes.iter()
+                    .filter(|process| process_matches(process, &matcher))
+                    .cloned()
+                    .collect();
+
+                // Initiators seen for the first time only record what's already running
+                if let Some(previous_matching) = previous.get(&key) {
+                    let event: &str = initiator.data.app_event.as_deref().unwrap_or("launch");
+                    let process: Option<&ProcessInfo> = get_process_event(event, previous_matching, &matching);
+
+                    if let Some(process) = process {
+                        let variables = get_initiator_variables(&initiator, vec![
+                            ("pid", VariableValue::Number(process.pid as f64)),
+                            ("name", VariableValue::String(process.name.clone())),
+                            ("path", VariableValue::String(
+                                process.path.as_ref().map(|path| path.to_string_lossy().to_string()).unwrap_or_default()
+                            )),
+                        ]);
+                        run_macro_initiator_with_variables(initiator.clone(), index, macro_.clone(), variables);
+                    }
+                }
+
+                current.insert(key, matching);
+            }
+        }
+
+        previous = current;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// The process an "exit" or "launch" event is for, if the matching processes changed that way since the last poll
+fn get_process_event<'a>(event: &str, previous: &'a [ProcessInfo], current: &'a [ProcessInfo]) -> Option<&'a ProcessInfo> {
+    match event {
+        "exit" if !previous.is_empty() && current.is_empty() => previous.last(),
+        "launch" if previous.is_empty() && !current.is_empty() => current.first(),
+        _ => None
+    }
+}
+
+enum AppMatcher {
+    Path(PathBuf),
+    Name(String),
+}
+
+// The app path can either be a full path to the executable or just its name, like "code" or "Code.exe"
+fn get_app_matcher(app_path: &str) -> AppMatcher {
+    if app_path.contains('/') || app_path.contains('\\') {
+        let path: &Path = Path::new(app_path);
+        AppMatcher::Path(path.canonicalize().unwrap_or(path.to_path_buf()))
+    } else {
+        AppMatcher::Name(strip_exe_extension(app_path).to_lowercase())
+    }
+}
+
+fn process_matches(process: &ProcessInfo, matcher: &AppMatcher) -> bool {
+    match matcher {
+        AppMatcher::Path(app_path) => match &process.path {
+            Some(path) if cfg!(target_os = "windows") => path.to_string_lossy().to_lowercase() == app_path.to_string_lossy().to_lowercase(),
+            Some(path) => path == app_path,
+            None => false
+        },
+        AppMatcher::Name(name) => {
+            let file_name: Option<String> = process.path.as_ref()
+                .and_then(|path| path.file_name())
+                .map(|file_name| strip_exe_extension(&file_name.to_string_lossy()).to_lowercase());
+
+            file_name.as_ref() == Some(name) || strip_exe_extension(&process.name).to_lowercase() == *name
+        }
+    }
+}
+
+fn strip_exe_extension(name: &str) -> &str {
+    match name.len().checked_sub(4) {
+        Some(index) if name.is_char_boundary(index) && name[index..].eq_ignore_ascii_case(".exe") => &name[..index],
+        _ => name
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_processes() -> Vec<ProcessInfo> {
+    let mut processes: Vec<ProcessInfo> = Vec::new();
+
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return processes
+    };
+
+    for entry in entries.flatten() {
+        let pid: u32 = match entry.file_name().to_string_lossy().parse() {
+            Ok(pid) => pid,
+            Err(_) => continue
+        };
+        let process_dir: PathBuf = entry.path();
+
+        // The exe link can't be read for other users' processes, and ends with " (deleted)" if the binary was replaced
+        let path: Option<PathBuf> = fs::read_link(process_dir.join("exe")).ok().map(|path| {
+            let path_string: String = path.to_string_lossy().to_string();
+            match path_string.strip_suffix(" (deleted)") {
+                Some(stripped) => PathBuf::from(stripped),
+                None => path
+            }
+        });
+
+        // comm is truncated to 15 characters, so prefer the executable's file name
+        let name: String = path.as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .or_else(|| fs::read_to_string(process_dir.join("comm")).ok().map(|comm| comm.trim().to_string()))
+            .unwrap_or_default();
+
+        processes.push(ProcessInfo { pid, name, path });
+    }
+
+    processes
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_processes() -> Vec<ProcessInfo> {
+    use sysinfo::{ PidExt, ProcessExt, System, SystemExt };
+
+    let mut system: System = System::new();
+    system.refresh_processes();
+
+    system.processes().iter().map(|(pid, process)| {
+        let path: &Path = process.exe();
+        ProcessInfo {
+            pid: pid.as_u32(),
+            name: process.name().to_string(),
+            path: if path.as_os_str().is_empty() { None } else { Some(path.to_path_buf()) },
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::{ Child, Command };
+
+    fn get_matching(matcher: &AppMatcher, pid: u32) -> Vec<ProcessInfo> {
+        get_processes().into_iter()
+            .filter(|process| process.pid == pid && process_matches(process, matcher))
+            .collect()
+    }
+
+    #[test]
+    fn strips_exe_extension() {
+        assert_eq!(strip_exe_extension("Code.exe"), "Code");
+        assert_eq!(strip_exe_extension("code.EXE"), "code");
+        assert_eq!(strip_exe_extension("code"), "code");
+        assert_eq!(strip_exe_extension(".exe"), "");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sees_a_process_launch_and_exit() {
+        let mut child: Child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid: u32 = child.id();
+        // "sleep" can be a link to a multicall binary like busybox, so match whatever the child's executable really is
+        let process: ProcessInfo = get_processes().into_iter().find(|process| process.pid == pid).unwrap();
+        let matcher: AppMatcher = get_app_matcher(&process.path.unwrap().to_string_lossy());
+
+        let launched: Vec<ProcessInfo> = get_matching(&matcher, pid);
+        let process: Option<&ProcessInfo> = get_process_event("launch", &[], &launched);
+        assert_eq!(process.map(|process| process.pid), Some(pid));
+        assert!(get_process_event("exit", &[], &launched).is_none());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let exited: Vec<ProcessInfo> = get_matching(&matcher, pid);
+        let process: Option<&ProcessInfo> = get_process_event("exit", &launched, &exited);
+        assert_eq!(process.map(|process| process.pid), Some(pid));
+        assert!(get_process_event("launch", &launched, &exited).is_none());
+    }
+}
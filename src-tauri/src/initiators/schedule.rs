@@ -0,0 +1,340 @@
+use super::super::get_macros;
+use super::super::execute::{ run_macro_initiator, is_initiator_active };
+use super::Initiator;
+use super::super::Macro;
+use super::super::paths::get_state_path;
+
+use chrono::{ DateTime, Duration, Local, NaiveDateTime, NaiveTime, TimeZone, Datelike };
+use rand::Rng;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
+use std::thread;
+use once_cell::sync::Lazy;
+
+// Every call to listen_initiator_schedule starts a new generation, which stops the previous scheduler thread
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+// Missed occurrences are only checked the first time the macros are loaded after startup. Shared with the cron initiator.
+static CHECKED_MISSED: AtomicBool = AtomicBool::new(false);
+// Cron jobs and the scheduler both record runs, so saving has to wait for the other to finish
+static LAST_RUNS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+// When each job runs next, kept across reloads so saving the macros doesn't restart every interval.
+// The scheduler thread holds this while it runs jobs, so a reload can't start a job the old thread just ran.
+static NEXT_OCCURRENCES: Lazy<Mutex<HashMap<String, Option<DateTime<Local>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+const SCHEDULE_TYPES: [&str; 3] = ["once", "interval", "weekly"];
+
+struct ScheduledJob {
+    key: String,
+    initiator: Initiator,
+    initiator_index: usize,
+    macro_: Macro,
+    next: Option<DateTime<Local>>,
+}
+
+// True the first time it's called, when missed occurrences should be run
+pub fn take_missed_check() -> bool {
+    !CHECKED_MISSED.swap(true, Ordering::SeqCst)
+}
+
+pub fn listen_initiator_schedule(check_missed: bool) {
+    let generation: u64 = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    let last_runs: HashMap<String, i64> = get_last_runs();
+    let now: DateTime<Local> = Local::now();
+
+    let mut next_occurrences = NEXT_OCCURRENCES.lock().unwrap_or_else(|error| error.into_inner());
+    let previous_occurrences: HashMap<String, Option<DateTime<Local>>> = std::mem::take(&mut *next_occurrences);
+    let mut jobs: Vec<ScheduledJob> = Vec::new();
+
+    for macro_ in get_macros() {
+        let initiators: Vec<Initiator> = macro_.clone().macro_.initiators.unwrap_or(vec![]);
+        for (index, initiator) in initiators.into_iter().enumerate() {
+            if !SCHEDULE_TYPES.contains(&initiator.type_.as_str()) {
+                continue;
+            }
+
+            let key: String = get_last_run_key(&macro_, &initiator);
+            let last_run: Option<DateTime<Local>> = last_runs.get(&key).and_then(|time| Local.timestamp_opt(*time, 0).single());
+
+            if check_missed && initiator.data.run_missed.unwrap_or(false) && was_missed(&initiator, last_run, now) {
+                println!("Running missed occurrence of macro \"{}\"", macro_.name);
+                run_scheduled_initiator(&key, &initiator, index, &macro_);
+            }
+
+            // The key changes with the schedule, so a job that's still there has the same schedule as before
+            let next: Option<DateTime<Local>> = match previous_occurrences.get(&key) {
+                Some(next) => *next,
+                None => get_next_occurrence(&initiator, now)
+            };
+            next_occurrences.insert(key.clone(), next);
+            jobs.push(ScheduledJob {
+                key,
+                next,
+                initiator,
+                initiator_index: index,
+                macro_: macro_.clone(),
+            });
+        }
+    }
+    drop(next_occurrences);
+
+    if jobs.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        {
+            let mut next_occurrences = NEXT_OCCURRENCES.lock().unwrap_or_else(|error| error.into_inner());
+            if GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            let now: DateTime<Local> = Local::now();
+            for job in jobs.iter_mut() {
+                match job.next {
+                    Some(next) if next <= now => {
+                        run_scheduled_initiator(&job.key, &job.initiator, job.initiator_index, &job.macro_);
+                        job.next = get_next_occurrence(&job.initiator, now);
+                        next_occurrences.insert(job.key.clone(), job.next);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        thread::sleep(std::time::Duration::from_millis(250));
+    });
+}
+
+// Initiators that are disabled or paused don't run, so they aren't recorded as having run either
+pub fn run_scheduled_initiator(key: &str, initiator: &Initiator, initiator_index: usize, macro_: &Macro) {
+    if !is_initiator_active(initiator, macro_) {
+        return;
+    }
+    record_last_run(key, Local::now());
+    run_macro_initiator(initiator.clone(), initiator_index, macro_.clone());
+}
+
+fn get_next_occurrence(initiator: &Initiator, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    match initiator.type_.as_str() {
+        "once" => {
+            let datetime: DateTime<Local> = parse_datetime(initiator.data.datetime.as_ref()?)?;
+            if datetime > after { Some(datetime) } else { None }
+        }
+        "interval" => {
+            let period: Duration = get_interval_period(initiator)?;
+            let jitter: f64 = initiator.data.jitter.unwrap_or(0.0).max(0.0);
+            let jitter_millis: i64 = if jitter > 0.0 {
+                rand::thread_rng().gen_range(0..=((jitter * 1000.0) as i64))
+            } else {
+                0
+            };
+            Some(after + period + Duration::milliseconds(jitter_millis))
+        }
+        "weekly" => get_next_weekly(initiator, after),
+        _ => None
+    }
+}
+
+fn was_missed(initiator: &Initiator, last_run: Option<DateTime<Local>>, now: DateTime<Local>) -> bool {
+    match initiator.type_.as_str() {
+        "once" => {
+            // A one-shot initiator that never ran is missed once its time has passed
+            let datetime: Option<DateTime<Local>> = initiator.data.datetime.as_ref().and_then(|datetime| parse_datetime(datetime));
+            match datetime {
+                Some(datetime) => datetime <= now && last_run.map_or(true, |last_run| last_run < datetime),
+                None => false
+            }
+        }
+        "interval" | "weekly" => {
+            match last_run {
+                Some(last_run) => {
+                    // Ignore jitter here so a missed interval doesn't depend on a random roll
+                    let next: Option<DateTime<Local>> = if initiator.type_ == "interval" {
+                        get_interval_period(initiator).map(|period| last_run + period)
+                    } else {
+                        get_next_weekly(initiator, last_run)
+                    };
+                    next.map_or(false, |next| next <= now)
+                }
+                None => false
+            }
+        }
+        _ => false
+    }
+}
+
+fn get_interval_period(initiator: &Initiator) -> Option<Duration> {
+    let interval: f64 = initiator.data.interval?;
+    // Minutes is what the interval initiator starts with in the frontend
+    let multiplier: f64 = match initiator.data.interval_unit.as_deref().unwrap_or("minutes") {
+        "seconds" => 1.0,
+        "hours" => 3600.0,
+        _ => 60.0
+    };
+    let millis: i64 = (interval * multiplier * 1000.0) as i64;
+    // Don't let a zero or negative interval spin the scheduler
+    if millis < 1000 {
+        return None;
+    }
+    Some(Duration::milliseconds(millis))
+}
+
+fn get_next_weekly(initiator: &Initiator, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    let time: NaiveTime = parse_time(initiator.data.local_time.as_ref()?)?;
+    let weekdays: &Vec<String> = initiator.data.weekdays.as_ref()?;
+
+    // Look one day past a full week so today's time is still found a week later
+    for day in 0..8 {
+        let date = after.naive_local().date() + Duration::days(day);
+        let weekday: String = format!("{:?}", date.weekday()).to_lowercase();
+        if !weekdays.iter().any(|selected| selected.to_lowercase() == weekday) {
+            continue;
+        }
+        // Times skipped by a daylight saving change don't exist locally, so just move on to the next day
+        if let Some(candidate) = Local.from_local_datetime(&date.and_time(time)).earliest() {
+            if candidate > after {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+fn parse_datetime(datetime: &str) -> Option<DateTime<Local>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(datetime) {
+        return Some(datetime.with_timezone(&Local));
+    }
+    // The frontend uses datetime-local inputs, which don't include a timezone
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(datetime, format) {
+            return Local.from_local_datetime(&datetime).earliest();
+        }
+    }
+    None
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok()
+}
+
+// Keyed by the schedule rather than where the initiator is in the macro, so reordering initiators doesn't mix their
+// records up. Changing the schedule starts its record over.
+pub fn get_last_run_key(macro_: &Macro, initiator: &Initiator) -> String {
+    let data = &initiator.data;
+    let schedule: serde_json::Value = serde_json::json!([
+        initiator.type_, data.cron, data.datetime, data.interval, data.interval_unit, data.weekdays, data.local_time
+    ]);
+    format!("{}#{}", macro_.name, schedule)
+}
+
+fn get_last_runs_path() -> Option<PathBuf> {
+    get_state_path("last_runs.json")
+}
+
+pub fn get_last_runs() -> HashMap<String, i64> {
+    get_last_runs_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn record_last_run(key: &str, time: DateTime<Local>) {
+    let path: PathBuf = match get_last_runs_path() {
+        Some(path) => path,
+        None => return
+    };
+
+    let _lock = LAST_RUNS_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let mut last_runs: HashMap<String, i64> = get_last_runs();
+    last_runs.insert(key.to_string(), time.timestamp());
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(error) = fs::write(&path, serde_json::to_string(&last_runs).unwrap_or_default()) {
+        println!("Failed to save the last run record: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn initiator(type_: &str, data: serde_json::Value) -> Initiator {
+        serde_json::from_value(serde_json::json!({ "type_": type_, "data": data, "executes": [], "variables": null })).unwrap()
+    }
+
+    fn local(datetime: &str) -> DateTime<Local> {
+        Local.from_local_datetime(&NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn gets_interval_periods() {
+        assert_eq!(get_interval_period(&initiator("interval", serde_json::json!({ "interval": 10.0 }))), Some(Duration::minutes(10)));
+        assert_eq!(get_interval_period(&initiator("interval", serde_json::json!({ "interval": 30.0, "interval_unit": "seconds" }))), Some(Duration::seconds(30)));
+        assert_eq!(get_interval_period(&initiator("interval", serde_json::json!({ "interval": 1.5, "interval_unit": "hours" }))), Some(Duration::minutes(90)));
+        assert_eq!(get_interval_period(&initiator("interval", serde_json::json!({ "interval": 0.5, "interval_unit": "seconds" }))), None);
+        assert_eq!(get_interval_period(&initiator("interval", serde_json::json!({}))), None);
+    }
+
+    #[test]
+    fn gets_the_next_weekly_time() {
+        // January 1st 2024 was a Monday
+        let weekly = |weekdays: &[&str], time: &str| initiator("weekly", serde_json::json!({ "weekdays": weekdays, "local_time": time }));
+        let after: DateTime<Local> = local("2024-01-01 10:00");
+
+        assert_eq!(get_next_weekly(&weekly(&["mon", "wed"], "09:00"), after), Some(local("2024-01-03 09:00")));
+        assert_eq!(get_next_weekly(&weekly(&["Mon"], "11:30"), after), Some(local("2024-01-01 11:30")));
+        assert_eq!(get_next_weekly(&weekly(&["mon"], "10:00"), after), Some(local("2024-01-08 10:00")));
+        assert_eq!(get_next_weekly(&weekly(&[], "10:00"), after), None);
+        assert_eq!(get_next_weekly(&weekly(&["mon"], "not a time"), after), None);
+    }
+
+    #[test]
+    fn finds_missed_occurrences() {
+        let now: DateTime<Local> = local("2024-01-01 10:00");
+
+        let once: Initiator = initiator("once", serde_json::json!({ "datetime": "2024-01-01T09:00" }));
+        assert!(was_missed(&once, None, now));
+        assert!(!was_missed(&once, Some(local("2024-01-01 09:00")), now));
+        assert!(!was_missed(&initiator("once", serde_json::json!({ "datetime": "2024-01-01T11:00" })), None, now));
+
+        let interval: Initiator = initiator("interval", serde_json::json!({ "interval": 10.0, "interval_unit": "minutes" }));
+        assert!(was_missed(&interval, Some(local("2024-01-01 09:45")), now));
+        assert!(!was_missed(&interval, Some(local("2024-01-01 09:55")), now));
+        assert!(!was_missed(&interval, None, now));
+
+        let weekly: Initiator = initiator("weekly", serde_json::json!({ "weekdays": ["mon"], "local_time": "09:00" }));
+        assert!(was_missed(&weekly, Some(local("2023-12-25 09:00")), now));
+        assert!(!was_missed(&weekly, Some(local("2024-01-01 09:00")), now));
+    }
+
+    #[test]
+    fn parses_datetimes() {
+        assert_eq!(parse_datetime("2024-01-01T10:00"), Some(local("2024-01-01 10:00")));
+        assert_eq!(parse_datetime("2024-01-01T10:00:00"), Some(local("2024-01-01 10:00")));
+        assert_eq!(
+            parse_datetime("2024-01-01T10:00:00+00:00"),
+            Some(DateTime::parse_from_rfc3339("2024-01-01T10:00:00Z").unwrap().with_timezone(&Local))
+        );
+        assert_eq!(parse_datetime("tomorrow"), None);
+    }
+
+    #[test]
+    fn keys_last_runs_by_schedule() {
+        let macro_: Macro = serde_json::from_value(serde_json::json!({
+            "name": "Schedule test", "description": "", "macro_": { "initiators": [], "functions": null }
+        })).unwrap();
+        let interval: Initiator = initiator("interval", serde_json::json!({ "interval": 10.0, "run_missed": false }));
+        let run_missed: Initiator = initiator("interval", serde_json::json!({ "interval": 10.0, "run_missed": true }));
+        let longer: Initiator = initiator("interval", serde_json::json!({ "interval": 20.0 }));
+
+        assert_eq!(get_last_run_key(&macro_, &interval), get_last_run_key(&macro_, &run_missed));
+        assert_ne!(get_last_run_key(&macro_, &interval), get_last_run_key(&macro_, &longer));
+    }
+}
@@ -0,0 +1,240 @@
+use super::super::execute::{ find_macro_initiator, get_json_variables, start_macro_run, is_initiator_active };
+use super::super::settings::{ get_settings, Settings };
+
+use once_cell::sync::Lazy;
+use tiny_http::{ Header, Method, Request, Response, Server };
+
+use std::io::Read;
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use std::time::Duration;
+
+static SERVER: Lazy<Mutex<Option<Arc<Server>>>> = Lazy::new(|| Mutex::new(None));
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 7523;
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+// Starts, restarts or stops the local HTTP listener depending on the current settings.
+// `POST /macros/<macro name>/<initiator index>` runs that initiator, with the JSON body's fields as variables.
+pub fn listen_initiator_webhook() {
+    let mut server_lock = SERVER.lock().unwrap();
+    if let Some(server) = server_lock.take() {
+        server.unblock();
+    }
+
+    let settings: Settings = get_settings();
+    if !settings.webhook_enabled {
+        return;
+    }
+
+    let token: String = settings.webhook_token.unwrap_or_default();
+    if token.trim().is_empty() {
+        println!("Not starting the webhook listener because no token is set");
+        return;
+    }
+
+    let address: String = format!(
+        "{}:{}",
+        settings.webhook_address.filter(|address| !address.trim().is_empty()).unwrap_or(DEFAULT_ADDRESS.to_string()),
+        settings.webhook_port.unwrap_or(DEFAULT_PORT)
+    );
+
+    // The previous listener might not have released the port yet
+    let mut server: Option<Server> = None;
+    for _ in 0..10 {
+        match Server::http(&address) {
+            Ok(new_server) => {
+                server = Some(new_server);
+                break;
+            }
+            Err(error) => {
+                println!("Failed to start the webhook listener on {}: {}", address, error);
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+    let server: Arc<Server> = match server {
+        Some(server) => Arc::new(server),
+        None => return
+    };
+
+    println!("Listening for webhooks on {}", address);
+    *server_lock = Some(server.clone());
+    serve(server, token);
+}
+
+fn serve(server: Arc<Server>, token: String) {
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle_request(request, &token);
+        }
+    });
+}
+
+fn handle_request(mut request: Request, token: &str) {
+    let (status, body): (u16, serde_json::Value) = match get_response(&mut request, token) {
+        Ok(run_id) => (200, serde_json::json!({ "runId": run_id })),
+        Err((status, error)) => (status, serde_json::json!({ "error": error }))
+    };
+
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    let _ = request.respond(response);
+}
+
+fn get_response(request: &mut Request, token: &str) -> Result<u64, (u16, String)> {
+    let authorization: Option<String> = request.headers().iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.to_string());
+    if !authorization.map_or(false, |authorization| tokens_equal(&authorization, &format!("Bearer {}", token))) {
+        return Err((401, "Missing or invalid bearer token".to_string()));
+    }
+
+    if *request.method() != Method::Post {
+        return Err((405, "Only POST requests are supported".to_string()));
+    }
+
+    let path: String = request.url().split('?').next().unwrap_or("").to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if segments.len() != 3 || segments[0] != "macros" {
+        return Err((404, "Expected a path like /macros/<macro>/<initiator>".to_string()));
+    }
+
+    let macro_name: String = decode_url_segment(segments[1]);
+    let initiator_index: usize = segments[2].parse()
+        .map_err(|_| (400, format!("\"{}\" is not an initiator index", segments[2])))?;
+
+    let mut body: String = String::new();
+    request.as_reader().take(MAX_BODY_SIZE).read_to_string(&mut body)
+        .map_err(|error| (400, format!("Failed to read the request body: {}", error)))?;
+
+    let json: serde_json::Value = if body.trim().is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_str(&body).map_err(|error| (400, format!("Invalid JSON body: {}", error)))?
+    };
+    let variables = get_json_variables(&json).map_err(|error| (400, error))?;
+
+    let (macro_, initiator) = find_macro_initiator(&macro_name, initiator_index).map_err(|error| (404, error))?;
+    if !is_initiator_active(&initiator, &macro_) {
+        return Err((409, format!("Macro \"{}\" or its initiator is disabled, or macros are paused", macro_name)));
+    }
+
+    Ok(start_macro_run(initiator, initiator_index, macro_, variables, "webhook".to_string()))
+}
+
+// Compares every byte so the time taken doesn't reveal how much of the token was right
+fn tokens_equal(a: &str, b: &str) -> bool {
+    let a: &[u8] = a.as_bytes();
+    let b: &[u8] = b.as_bytes();
+    let mut difference: u8 = (a.len() != b.len()) as u8;
+    for index in 0..a.len().max(b.len()) {
+        difference |= a.get(index).unwrap_or(&0) ^ b.get(index).unwrap_or(&0);
+    }
+    difference == 0
+}
+
+// Macro names can contain spaces and other characters that are percent-encoded in URLs
+fn decode_url_segment(segment: &str) -> String {
+    let bytes: &[u8] = segment.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut index: usize = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex: Option<u8> = std::str::from_utf8(&bytes[index + 1..index + 3]).ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{ use_test_macros, Macro };
+    use super::super::super::paths::use_test_data_dir;
+
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::sync::MutexGuard;
+
+    const TOKEN: &str = "secret";
+
+    // The macros stay in place until the guard is dropped at the end of the test
+    fn start_server() -> (u16, MutexGuard<'static, ()>) {
+        use_test_data_dir();
+        let macros: Vec<Macro> = serde_json::from_value(serde_json::json!([
+            { "name": "Webhook test", "description": "", "macro_": { "initiators": [
+                { "type_": "webhook", "data": {}, "executes": [], "variables": null }
+            ], "functions": null } },
+            { "name": "Webhook test disabled", "description": "", "enabled": false, "macro_": { "initiators": [
+                { "type_": "webhook", "data": {}, "executes": [], "variables": null }
+            ], "functions": null } }
+        ])).unwrap();
+        let macros_guard: MutexGuard<()> = use_test_macros(macros);
+
+        let server: Server = Server::http("127.0.0.1:0").unwrap();
+        let port: u16 = server.server_addr().to_ip().unwrap().port();
+        serve(Arc::new(server), TOKEN.to_string());
+        (port, macros_guard)
+    }
+
+    fn send(port: u16, path: &str, authorization: Option<&str>, body: &str) -> (u16, serde_json::Value) {
+        let mut stream: TcpStream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let authorization: String = authorization.map(|value| format!("Authorization: {}\r\n", value)).unwrap_or_default();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path, authorization, body.len(), body
+        ).unwrap();
+
+        let mut response: String = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status: u16 = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body: &str = response.split("\r\n\r\n").nth(1).unwrap_or("");
+        (status, serde_json::from_str(body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[test]
+    fn decodes_url_segments() {
+        assert_eq!(decode_url_segment("My%20macro"), "My macro");
+        assert_eq!(decode_url_segment("caf%C3%A9"), "café");
+        assert_eq!(decode_url_segment("100%"), "100%");
+        assert_eq!(decode_url_segment("%zz"), "%zz");
+    }
+
+    #[test]
+    fn rejects_bad_tokens() {
+        let (port, _macros_guard) = start_server();
+        assert_eq!(send(port, "/macros/Webhook%20test/0", None, "").0, 401);
+        assert_eq!(send(port, "/macros/Webhook%20test/0", Some("Bearer wrong"), "").0, 401);
+        assert_eq!(send(port, "/macros/Webhook%20test/0", Some(TOKEN), "").0, 401);
+    }
+
+    #[test]
+    fn rejects_disabled_initiators() {
+        let (port, _macros_guard) = start_server();
+        let (status, _) = send(port, "/macros/Webhook%20test%20disabled/0", Some("Bearer secret"), "");
+        assert_eq!(status, 409);
+    }
+
+    #[test]
+    fn runs_the_initiator() {
+        let (port, _macros_guard) = start_server();
+        let (status, body) = send(port, "/macros/Webhook%20test/0", Some("Bearer secret"), r#"{ "name": "value" }"#);
+        assert_eq!(status, 200);
+        assert!(body["runId"].is_u64());
+
+        assert_eq!(send(port, "/macros/Webhook%20test/1", Some("Bearer secret"), "").0, 404);
+        assert_eq!(send(port, "/macros/Webhook%20test/0", Some("Bearer secret"), "not json").0, 400);
+    }
+}
@@ -0,0 +1,297 @@
+use inputbot::{ KeybdKey, MouseButton, MouseCursor, MouseWheel };
+
+use serde::Serialize;
+
+use std::fmt;
+use std::sync::{ Arc, Mutex };
+
+use super::keys::{ get_key_name, get_button_name, tap_character };
+use super::typing::UnicodeTyper;
+#[cfg(target_os = "linux")]
+use super::settings::uses_uinput;
+#[cfg(target_os = "linux")]
+use super::uinput::UinputBackend;
+
+// Modifiers and mouse buttons are what usually get stuck, so releasing everything lets go of them even if no run pressed them
+static STUCK_KEYS: [KeybdKey; 8] = [
+    KeybdKey::LShiftKey, KeybdKey::RShiftKey, KeybdKey::LControlKey, KeybdKey::RControlKey,
+    KeybdKey::LAltKey, KeybdKey::RAltKey, KeybdKey::LSuper, KeybdKey::RSuper,
+];
+static STUCK_BUTTONS: [MouseButton; 3] = [ MouseButton::LeftButton, MouseButton::RightButton, MouseButton::MiddleButton ];
+
+// Where the keyboard and mouse input from executions goes. Each run has its own, so dry runs can record input instead of sending it.
+pub trait InputBackend: Send {
+    fn press_key(&mut self, key: KeybdKey);
+    fn release_key(&mut self, key: KeybdKey);
+    // Types a character there isn't a key for. Returns false if it can't be typed.
+    fn type_unicode(&mut self, character: char) -> bool;
+    fn press_button(&mut self, button: MouseButton);
+    fn release_button(&mut self, button: MouseButton);
+    fn move_mouse_relative(&mut self, x: i32, y: i32);
+    fn move_mouse_absolute(&mut self, x: i32, y: i32);
+    fn scroll_vertical(&mut self, amount: i32);
+    fn scroll_horizontal(&mut self, amount: i32);
+    // Errors if the backend has no way of knowing where the mouse is
+    fn get_mouse_position(&self) -> Result<(i32, i32), String>;
+
+    fn type_character(&mut self, character: char) -> bool {
+        tap_character(self, character) || self.type_unicode(character)
+    }
+}
+
+// Sends input to the OS
+#[derive(Default)]
+pub struct InputbotBackend {
+    // Only set up if a character needs it, since it can mean connecting to the display server
+    typer: Option<UnicodeTyper>,
+}
+
+impl InputBackend for InputbotBackend {
+    fn press_key(&mut self, key: KeybdKey) {
+        key.press();
+    }
+
+    fn release_key(&mut self, key: KeybdKey) {
+        key.release();
+    }
+
+    fn type_unicode(&mut self, character: char) -> bool {
+        self.typer.get_or_insert_with(UnicodeTyper::new).type_character(character)
+    }
+
+    fn press_button(&mut self, button: MouseButton) {
+        button.press();
+    }
+
+    fn release_button(&mut self, button: MouseButton) {
+        button.release();
+    }
+
+    fn move_mouse_relative(&mut self, x: i32, y: i32) {
+        MouseCursor::move_rel(x, y);
+    }
+
+    fn move_mouse_absolute(&mut self, x: i32, y: i32) {
+        MouseCursor::move_abs(x, y);
+    }
+
+    fn scroll_vertical(&mut self, amount: i32) {
+        MouseWheel::scroll_ver(amount);
+    }
+
+    fn scroll_horizontal(&mut self, amount: i32) {
+        MouseWheel::scroll_hor(amount);
+    }
+
+    fn get_mouse_position(&self) -> Result<(i32, i32), String> {
+        Ok(MouseCursor::pos())
+    }
+
+    fn type_character(&mut self, character: char) -> bool {
+        // Windows can type anything as Unicode, which doesn't depend on the keyboard layout
+        if cfg!(target_os = "windows") && !character.is_control() {
+            return self.type_unicode(character);
+        }
+        tap_character(self, character) || self.type_unicode(character)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum InputEvent {
+    PressKey { key: String },
+    ReleaseKey { key: String },
+    TypeCharacter { character: char },
+    PressButton { button: String },
+    ReleaseButton { button: String },
+    MoveMouseRelative { x: i32, y: i32 },
+    MoveMouseAbsolute { x: i32, y: i32 },
+    ScrollVertical { amount: i32 },
+    ScrollHorizontal { amount: i32 },
+}
+
+// Keeps the input instead of sending it, and keeps track of where the mouse would be
+#[derive(Default)]
+pub struct RecordingBackend {
+    events: Arc<Mutex<Vec<InputEvent>>>,
+    position: (i32, i32),
+}
+
+impl RecordingBackend {
+    // The events stay readable after the backend is given to a run
+    pub fn get_events(&self) -> Arc<Mutex<Vec<InputEvent>>> {
+        self.events.clone()
+    }
+
+    fn record(&mut self, event: InputEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl InputBackend for RecordingBackend {
+    fn press_key(&mut self, key: KeybdKey) {
+        self.record(InputEvent::PressKey { key: get_key_label(key) });
+    }
+
+    fn release_key(&mut self, key: KeybdKey) {
+        self.record(InputEvent::ReleaseKey { key: get_key_label(key) });
+    }
+
+    fn type_unicode(&mut self, character: char) -> bool {
+        self.record(InputEvent::TypeCharacter { character });
+        true
+    }
+
+    fn press_button(&mut self, button: MouseButton) {
+        self.record(InputEvent::PressButton { button: get_button_label(button) });
+    }
+
+    fn release_button(&mut self, button: MouseButton) {
+        self.record(InputEvent::ReleaseButton { button: get_button_label(button) });
+    }
+
+    fn move_mouse_relative(&mut self, x: i32, y: i32) {
+        self.position = (self.position.0 + x, self.position.1 + y);
+        self.record(InputEvent::MoveMouseRelative { x, y });
+    }
+
+    fn move_mouse_absolute(&mut self, x: i32, y: i32) {
+        self.position = (x, y);
+        self.record(InputEvent::MoveMouseAbsolute { x, y });
+    }
+
+    fn scroll_vertical(&mut self, amount: i32) {
+        self.record(InputEvent::ScrollVertical { amount });
+    }
+
+    fn scroll_horizontal(&mut self, amount: i32) {
+        self.record(InputEvent::ScrollHorizontal { amount });
+    }
+
+    fn get_mouse_position(&self) -> Result<(i32, i32), String> {
+        Ok(self.position)
+    }
+}
+
+fn get_key_label(key: KeybdKey) -> String {
+    get_key_name(key).map(|name| name.to_string()).unwrap_or(format!("{:?}", key))
+}
+
+fn get_button_label(button: MouseButton) -> String {
+    get_button_name(button).map(|name| name.to_string()).unwrap_or(format!("{:?}", button))
+}
+
+// Keeps track of the keys and mouse buttons a run has pressed and not released yet, so they can be let go of when it ends
+pub struct TrackedInput {
+    backend: Box<dyn InputBackend>,
+    keys: Vec<KeybdKey>,
+    buttons: Vec<MouseButton>,
+}
+
+impl TrackedInput {
+    pub fn new(backend: Box<dyn InputBackend>) -> TrackedInput {
+        TrackedInput { backend, keys: Vec::new(), buttons: Vec::new() }
+    }
+
+    // Releases in the opposite order they were pressed, so modifiers go last like they would by hand
+    pub fn release_all(&mut self) {
+        for key in self.keys.drain(..).rev() {
+            self.backend.release_key(key);
+        }
+        for button in self.buttons.drain(..).rev() {
+            self.backend.release_button(button);
+        }
+    }
+}
+
+impl fmt::Debug for TrackedInput {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_struct("TrackedInput").field("keys", &self.keys).field("buttons", &self.buttons).finish()
+    }
+}
+
+impl InputBackend for TrackedInput {
+    fn press_key(&mut self, key: KeybdKey) {
+        self.backend.press_key(key);
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    fn release_key(&mut self, key: KeybdKey) {
+        self.backend.release_key(key);
+        self.keys.retain(|held| *held != key);
+    }
+
+    fn type_unicode(&mut self, character: char) -> bool {
+        self.backend.type_unicode(character)
+    }
+
+    fn press_button(&mut self, button: MouseButton) {
+        self.backend.press_button(button);
+        if !self.buttons.contains(&button) {
+            self.buttons.push(button);
+        }
+    }
+
+    fn release_button(&mut self, button: MouseButton) {
+        self.backend.release_button(button);
+        self.buttons.retain(|held| *held != button);
+    }
+
+    fn move_mouse_relative(&mut self, x: i32, y: i32) {
+        self.backend.move_mouse_relative(x, y);
+    }
+
+    fn move_mouse_absolute(&mut self, x: i32, y: i32) {
+        self.backend.move_mouse_absolute(x, y);
+    }
+
+    fn scroll_vertical(&mut self, amount: i32) {
+        self.backend.scroll_vertical(amount);
+    }
+
+    fn scroll_horizontal(&mut self, amount: i32) {
+        self.backend.scroll_horizontal(amount);
+    }
+
+    fn get_mouse_position(&self) -> Result<(i32, i32), String> {
+        self.backend.get_mouse_position()
+    }
+
+    // Typing taps keys without holding them, so it goes straight to the backend
+    fn type_character(&mut self, character: char) -> bool {
+        self.backend.type_character(character)
+    }
+}
+
+// The backend picked in the settings, falling back to inputbot if it can't be used
+pub fn create_input_backend() -> Box<dyn InputBackend> {
+    #[cfg(target_os = "linux")]
+    if uses_uinput() {
+        match UinputBackend::new() {
+            Ok(backend) => return Box::new(backend),
+            Err(error) => println!("{}. Using inputbot instead.", error)
+        }
+    }
+    Box::new(InputbotBackend::default())
+}
+
+// The OS ignores releases from a device that didn't press the key, so they go through every backend that's been used.
+// The backend setting could have changed since the keys were pressed.
+pub fn release_stuck_inputs() {
+    let mut backends: Vec<Box<dyn InputBackend>> = vec![Box::new(InputbotBackend::default())];
+    #[cfg(target_os = "linux")]
+    if let Some(backend) = UinputBackend::existing() {
+        backends.push(Box::new(backend));
+    }
+
+    for backend in backends.iter_mut() {
+        for key in STUCK_KEYS {
+            backend.release_key(key);
+        }
+        for button in STUCK_BUTTONS {
+            backend.release_button(button);
+        }
+    }
+}
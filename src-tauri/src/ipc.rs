@@ -0,0 +1,427 @@
+use super::get_macros;
+use super::execute::{ find_macro_initiator, start_macro_run, get_json_variables, is_initiator_active, Variables };
+use super::running::{ get_running_macros, stop_run, release_all_inputs, get_timestamp, RunningMacro };
+use super::Macro;
+
+use serde::{ Deserialize, Serialize };
+
+use std::io::{ self, BufRead, BufReader, Read, Write };
+use std::path::PathBuf;
+use std::thread;
+
+// Commands that can be run from the command line, like "codemacros trigger <macro>".
+// They're sent to the instance that's already running over a local socket, one JSON line each way, since the single
+// instance plugin can't send a reply or exit code back to the command.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum IpcRequest {
+    Trigger {
+        macro_name: String,
+        initiator: usize,
+        variables: serde_json::Value,
+    },
+    List,
+    Stop { id: u64 },
+    Release,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct IpcMessage {
+    // Only used where the socket can't be protected with file permissions
+    token: String,
+    #[serde(flatten)]
+    request: IpcRequest,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct IpcResponse {
+    success: bool,
+    message: String,
+}
+
+const USAGE: &str = "Usage:
+  codemacros [--data-dir <folder>]                      Start Code Macros, keeping its data in another folder
+                                                        (CODEMACROS_DATA_DIR works too)
+  codemacros trigger <macro> [--initiator <index>] [--var <name>=<value>]...
+                                                        Run an initiator of a macro (the first one by default)
+  codemacros list                                       List macros and the runs in progress
+  codemacros stop <run id>                              Stop a run
+  codemacros release                                    Release every key and mouse button macros are holding down";
+
+// Returns the exit code if the arguments are a command, or None if the app should start normally
+pub fn run_cli_command(args: &[String]) -> Option<i32> {
+    let request: Result<IpcRequest, String> = match args.first().map(|arg| arg.as_str()) {
+        Some("trigger") => parse_trigger_command(&args[1..]),
+        Some("list") if args.len() == 1 => Ok(IpcRequest::List),
+        Some("release") if args.len() == 1 => Ok(IpcRequest::Release),
+        Some("stop") => match args.get(1).map(|id| id.parse::<u64>()) {
+            Some(Ok(id)) if args.len() == 2 => Ok(IpcRequest::Stop { id }),
+            _ => Err("Expected a run ID, like \"codemacros stop 3\"".to_string())
+        },
+        Some("help") | Some("--help") | Some("-h") => {
+            attach_console();
+            println!("{}", USAGE);
+            return Some(0);
+        }
+        Some("list") => Err("\"list\" doesn't take any arguments".to_string()),
+        Some("release") => Err("\"release\" doesn't take any arguments".to_string()),
+        _ => return None
+    };
+
+    attach_console();
+
+    let request: IpcRequest = match request {
+        Ok(request) => request,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return Some(2);
+        }
+    };
+
+    match send_request(request) {
+        Ok(response) => {
+            if response.success {
+                println!("{}", response.message);
+                Some(0)
+            } else {
+                eprintln!("{}", response.message);
+                Some(1)
+            }
+        }
+        Err(error) => {
+            eprintln!("Couldn't connect to Code Macros, is it running? ({})", error);
+            Some(1)
+        }
+    }
+}
+
+fn parse_trigger_command(args: &[String]) -> Result<IpcRequest, String> {
+    let mut macro_name: Option<String> = None;
+    let mut initiator: usize = 0;
+    let mut variables: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--var" | "-v" => {
+                let variable: &String = args.next().ok_or("Expected a variable after --var, like --var name=value")?;
+                let (name, value) = variable.split_once('=').ok_or(format!("Variable \"{}\" should look like name=value", variable))?;
+                // Values that look like numbers are passed as numbers, like the webhook's JSON would
+                let value: serde_json::Value = match value.parse::<f64>() {
+                    Ok(number) if number.is_finite() => serde_json::json!(number),
+                    _ => serde_json::Value::String(value.to_string())
+                };
+                variables.insert(name.to_string(), value);
+            }
+            "--initiator" | "-i" => {
+                initiator = args.next()
+                    .and_then(|index| index.parse().ok())
+                    .ok_or("Expected an initiator index after --initiator")?;
+            }
+            _ if macro_name.is_none() => macro_name = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument \"{}\"", arg))
+        }
+    }
+
+    Ok(IpcRequest::Trigger {
+        macro_name: macro_name.ok_or("Expected the name of a macro to trigger")?,
+        initiator,
+        variables: serde_json::Value::Object(variables),
+    })
+}
+
+fn handle_request(request: IpcRequest) -> IpcResponse {
+    let result: Result<String, String> = match request {
+        IpcRequest::Trigger { macro_name, initiator, variables } => {
+            find_macro_initiator(&macro_name, initiator).and_then(|(macro_, initiator_data)| {
+                if !is_initiator_active(&initiator_data, &macro_) {
+                    return Err(format!("Macro \"{}\" or its initiator is disabled, or macros are paused", macro_name));
+                }
+                let variables: Variables = get_json_variables(&variables)?;
+                let run_id: u64 = start_macro_run(initiator_data, initiator, macro_, variables, "cli".to_string());
+                Ok(format!("Started run {}", run_id))
+            })
+        }
+        IpcRequest::List => Ok(get_list_message(get_macros(), get_running_macros())),
+        IpcRequest::Stop { id } => {
+            if stop_run(id) {
+                Ok(format!("Stopping run {}", id))
+            } else {
+                Err(format!("There is no run with ID {}", id))
+            }
+        }
+        IpcRequest::Release => {
+            release_all_inputs();
+            Ok("Released all keys and mouse buttons".to_string())
+        }
+    };
+
+    match result {
+        Ok(message) => IpcResponse { success: true, message },
+        Err(message) => IpcResponse { success: false, message }
+    }
+}
+
+fn get_list_message(macros: Vec<Macro>, running: Vec<RunningMacro>) -> String {
+    let mut lines: Vec<String> = vec!["Macros:".to_string()];
+    if macros.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for macro_ in macros {
+        lines.push(format!("  {}", macro_.name));
+        for (index, initiator) in macro_.macro_.initiators.unwrap_or(vec![]).iter().enumerate() {
+            lines.push(format!("    {}: {}", index, initiator.type_));
+        }
+    }
+
+    lines.push("Running:".to_string());
+    if running.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    let now: u64 = get_timestamp();
+    for run in running {
+        lines.push(format!(
+            "  {}  {} ({}, {}s)",
+            run.id, run.macro_name, run.trigger, now.saturating_sub(run.started) / 1000
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn handle_connection<S: Read + Write>(stream: S, token: &str) -> io::Result<()> {
+    let mut reader: BufReader<S> = BufReader::new(stream);
+    let mut line: String = String::new();
+    reader.read_line(&mut line)?;
+
+    let response: IpcResponse = match serde_json::from_str::<IpcMessage>(&line) {
+        Ok(message) if message.token == token => handle_request(message.request),
+        Ok(_) => IpcResponse { success: false, message: "Invalid token".to_string() },
+        Err(error) => IpcResponse { success: false, message: format!("Invalid request: {}", error) }
+    };
+
+    let stream: &mut S = reader.get_mut();
+    writeln!(stream, "{}", serde_json::to_string(&response).unwrap())?;
+    stream.flush()
+}
+
+fn send_request(request: IpcRequest) -> io::Result<IpcResponse> {
+    let (mut stream, token) = connect()?;
+    let message: IpcMessage = IpcMessage { token, request };
+    writeln!(stream, "{}", serde_json::to_string(&message).unwrap())?;
+    stream.flush()?;
+
+    let mut line: String = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+// On unix, a socket file in a folder that only the current user can open
+#[cfg(unix)]
+fn get_socket_dir() -> PathBuf {
+    // The runtime directory is private to the user, but the temp directory is shared, so include the user's name there
+    match tauri::api::path::runtime_dir() {
+        Some(runtime_dir) => runtime_dir.join("codemacros"),
+        None => std::env::temp_dir().join(format!("codemacros-{}", std::env::var("USER").unwrap_or_default()))
+    }
+}
+
+#[cfg(unix)]
+fn get_socket_path() -> PathBuf {
+    get_socket_dir().join("ipc.sock")
+}
+
+// Another user could make the folder first in a shared temp directory, so it's only used if it's ours and private
+#[cfg(unix)]
+fn create_socket_dir(socket_dir: &std::path::Path) -> io::Result<()> {
+    use std::fs::{ self, DirBuilder };
+    use std::os::unix::fs::{ DirBuilderExt, MetadataExt };
+
+    if let Err(error) = DirBuilder::new().mode(0o700).create(socket_dir) {
+        if error.kind() != io::ErrorKind::AlreadyExists {
+            return Err(error);
+        }
+    }
+
+    let metadata: fs::Metadata = fs::symlink_metadata(socket_dir)?;
+    if !metadata.is_dir() || !is_owned_by_user(&metadata) || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the folder belongs to another user or can be opened by them"));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_owned_by_user(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.uid() == unsafe { libc::getuid() }
+}
+
+#[cfg(unix)]
+pub fn listen_ipc() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{ UnixListener, UnixStream };
+
+    let socket_dir: PathBuf = get_socket_dir();
+    if let Err(error) = create_socket_dir(&socket_dir) {
+        println!("Failed to listen for commands in \"{}\": {}", socket_dir.display(), error);
+        return;
+    }
+    let socket_path: PathBuf = get_socket_path();
+
+    // A socket left behind by an instance that crashed can't be connected to, so it's safe to replace
+    let stale: bool = fs::symlink_metadata(&socket_path).map_or(false, |metadata| is_owned_by_user(&metadata))
+        && UnixStream::connect(&socket_path).is_err();
+    if stale {
+        let _ = fs::remove_file(&socket_path);
+    }
+
+    let listener: UnixListener = match UnixListener::bind(&socket_path) {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("Failed to listen for commands on \"{}\": {}", socket_path.display(), error);
+            return;
+        }
+    };
+    // The folder already keeps other users out, so the socket only has to be private once it exists
+    if let Err(error) = fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600)) {
+        println!("Failed to protect \"{}\": {}", socket_path.display(), error);
+        return;
+    }
+
+    for stream in listener.incoming().flatten() {
+        thread::spawn(move || {
+            if let Err(error) = handle_connection(stream, "") {
+                println!("Failed to handle a command: {}", error);
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+fn connect() -> io::Result<(std::os::unix::net::UnixStream, String)> {
+    Ok((std::os::unix::net::UnixStream::connect(get_socket_path())?, String::new()))
+}
+
+// Elsewhere, a TCP port on the loopback interface. Its port and a random token are written to a file in the
+// user's temp directory, so other users on the computer can't send commands.
+#[cfg(not(unix))]
+fn get_port_file_path() -> PathBuf {
+    std::env::temp_dir().join("codemacros-ipc")
+}
+
+#[cfg(not(unix))]
+pub fn listen_ipc() {
+    use rand::{ distributions::Alphanumeric, Rng };
+    use std::fs;
+    use std::net::TcpListener;
+
+    let listener: TcpListener = match TcpListener::bind("127.0.0.1:0") {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("Failed to listen for commands: {}", error);
+            return;
+        }
+    };
+    let port: u16 = match listener.local_addr() {
+        Ok(address) => address.port(),
+        Err(_) => return
+    };
+    let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+
+    if let Err(error) = fs::write(get_port_file_path(), format!("{} {}", port, token)) {
+        println!("Failed to write \"{}\": {}", get_port_file_path().display(), error);
+        return;
+    }
+
+    for stream in listener.incoming().flatten() {
+        let token: String = token.clone();
+        thread::spawn(move || {
+            if let Err(error) = handle_connection(stream, &token) {
+                println!("Failed to handle a command: {}", error);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn connect() -> io::Result<(std::net::TcpStream, String)> {
+    let contents: String = std::fs::read_to_string(get_port_file_path())?;
+    let (port, token) = contents.trim().split_once(' ')
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, "Invalid port file"))?;
+    let port: u16 = port.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid port file"))?;
+    Ok((std::net::TcpStream::connect(("127.0.0.1", port))?, token.to_string()))
+}
+
+// Release builds on Windows don't have a console, so output would go nowhere without attaching to the terminal
+// the command was run from
+#[cfg(target_os = "windows")]
+pub fn attach_console() {
+    unsafe {
+        winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn attach_console() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::running::{ start_run, MacroRun };
+    use super::super::paths::use_test_data_dir;
+
+    fn get_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_trigger_commands() {
+        match parse_trigger_command(&get_args(&["My macro", "--initiator", "2", "--var", "count=3", "-v", "name=a=b"])) {
+            Ok(IpcRequest::Trigger { macro_name, initiator, variables }) => {
+                assert_eq!(macro_name, "My macro");
+                assert_eq!(initiator, 2);
+                assert_eq!(variables, serde_json::json!({ "count": 3.0, "name": "a=b" }));
+            }
+            other => panic!("Unexpected result {:?}", other)
+        }
+
+        match parse_trigger_command(&get_args(&["My macro", "-v", "text=inf"])) {
+            Ok(IpcRequest::Trigger { initiator, variables, .. }) => {
+                assert_eq!(initiator, 0);
+                assert_eq!(variables, serde_json::json!({ "text": "inf" }));
+            }
+            other => panic!("Unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_bad_trigger_commands() {
+        assert!(parse_trigger_command(&get_args(&[])).is_err());
+        assert!(parse_trigger_command(&get_args(&["My macro", "Another"])).is_err());
+        assert!(parse_trigger_command(&get_args(&["My macro", "--var"])).is_err());
+        assert!(parse_trigger_command(&get_args(&["My macro", "--var", "name"])).is_err());
+        assert!(parse_trigger_command(&get_args(&["My macro", "--initiator", "first"])).is_err());
+    }
+
+    #[test]
+    fn lists_macros_and_runs() {
+        assert_eq!(get_list_message(vec![], vec![]), "Macros:\n  (none)\nRunning:\n  (none)");
+
+        use_test_data_dir();
+        let macros: Vec<Macro> = serde_json::from_value(serde_json::json!([
+            { "name": "List test", "description": "", "macro_": { "initiators": [
+                { "type_": "keypress", "data": {}, "executes": [], "variables": null },
+                { "type_": "webhook", "data": {}, "executes": [], "variables": null }
+            ], "functions": null } },
+            { "name": "Empty", "description": "", "macro_": { "initiators": null, "functions": null } }
+        ])).unwrap();
+        let run: MacroRun = start_run("List test", "cli");
+        let running: Vec<RunningMacro> = get_running_macros().into_iter().filter(|running| running.id == run.id).collect();
+
+        assert_eq!(get_list_message(macros, running), format!(
+            "Macros:\n  List test\n    0: keypress\n    1: webhook\n  Empty\nRunning:\n  {}  List test (cli, 0s)",
+            run.id
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+use inputbot::{ get_keybd_key, KeybdKey, MouseButton };
+
+use super::input::InputBackend;
+
+// Characters that are typed by holding shift on a US keyboard
+static SHIFTED_CHARACTERS: [char; 21] = [ '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '{', '}', '|', ':', '"', '<', '>', '?', '~' ];
+
+pub fn needs_shift(character: char) -> bool {
+    character.is_uppercase() || SHIFTED_CHARACTERS.contains(&character)
+}
+
+// The names keys have in the macro config. Keypress initiators match against these, and keys are sent by them too.
+// When two keys share a name, like the shift keys, the first one is the one that gets sent.
+static KEY_NAMES: &[(KeybdKey, &str)] = &[
+    (KeybdKey::AKey,          "a"),
+    (KeybdKey::BKey,          "b"),
+    (KeybdKey::CKey,          "c"),
+    (KeybdKey::DKey,          "d"),
+    (KeybdKey::EKey,          "e"),
+    (KeybdKey::FKey,          "f"),
+    (KeybdKey::GKey,          "g"),
+    (KeybdKey::HKey,          "h"),
+    (KeybdKey::IKey,          "i"),
+    (KeybdKey::JKey,          "j"),
+    (KeybdKey::KKey,          "k"),
+    (KeybdKey::LKey,          "l"),
+    (KeybdKey::MKey,          "m"),
+    (KeybdKey::NKey,          "n"),
+    (KeybdKey::OKey,          "o"),
+    (KeybdKey::PKey,          "p"),
+    (KeybdKey::QKey,          "q"),
+    (KeybdKey::RKey,          "r"),
+    (KeybdKey::SKey,          "s"),
+    (KeybdKey::TKey,          "t"),
+    (KeybdKey::UKey,          "u"),
+    (KeybdKey::VKey,          "v"),
+    (KeybdKey::WKey,          "w"),
+    (KeybdKey::XKey,          "x"),
+    (KeybdKey::YKey,          "y"),
+    (KeybdKey::ZKey,          "z"),
+    (KeybdKey::Numrow0Key,    "0"),
+    (KeybdKey::Numrow1Key,    "1"),
+    (KeybdKey::Numrow2Key,    "2"),
+    (KeybdKey::Numrow3Key,    "3"),
+    (KeybdKey::Numrow4Key,    "4"),
+    (KeybdKey::Numrow5Key,    "5"),
+    (KeybdKey::Numrow6Key,    "6"),
+    (KeybdKey::Numrow7Key,    "7"),
+    (KeybdKey::Numrow8Key,    "8"),
+    (KeybdKey::Numrow9Key,    "9"),
+    (KeybdKey::Numpad0Key,    "0"),
+    (KeybdKey::Numpad1Key,    "1"),
+    (KeybdKey::Numpad2Key,    "2"),
+    (KeybdKey::Numpad3Key,    "3"),
+    (KeybdKey::Numpad4Key,    "4"),
+    (KeybdKey::Numpad5Key,    "5"),
+    (KeybdKey::Numpad6Key,    "6"),
+    (KeybdKey::Numpad7Key,    "7"),
+    (KeybdKey::Numpad8Key,    "8"),
+    (KeybdKey::Numpad9Key,    "9"),
+    (KeybdKey::BackspaceKey,  "backspace"),
+    (KeybdKey::TabKey,        "tab"),
+    (KeybdKey::EnterKey,      "enter"),
+    (KeybdKey::EscapeKey,     "escape"),
+    (KeybdKey::SpaceKey,      "space"),
+    (KeybdKey::F1Key,         "f1"),
+    (KeybdKey::F2Key,         "f2"),
+    (KeybdKey::F3Key,         "f3"),
+    (KeybdKey::F4Key,         "f4"),
+    (KeybdKey::F5Key,         "f5"),
+    (KeybdKey::F6Key,         "f6"),
+    (KeybdKey::F7Key,         "f7"),
+    (KeybdKey::F8Key,         "f8"),
+    (KeybdKey::F9Key,         "f9"),
+    (KeybdKey::F10Key,        "f10"),
+    (KeybdKey::CapsLockKey,   "capslock"),
+    (KeybdKey::QuoteKey,      "'"),
+    (KeybdKey::SemicolonKey,  ";"),
+    (KeybdKey::CommaKey,      ","),
+    (KeybdKey::PeriodKey,     "."),
+    (KeybdKey::SlashKey,      "/"),
+    (KeybdKey::BackslashKey,  "\\"),
+    (KeybdKey::MinusKey,      "-"),
+    (KeybdKey::EqualKey,      "="),
+    (KeybdKey::LBracketKey,   "["),
+    (KeybdKey::RBracketKey,   "]"),
+    (KeybdKey::BackquoteKey,  "`"),
+    (KeybdKey::LShiftKey,     "shift"),
+    (KeybdKey::RShiftKey,     "shift"),
+    (KeybdKey::LControlKey,   "control"),
+    (KeybdKey::RControlKey,   "control"),
+    // Does not work on linux
+    (KeybdKey::RightKey,      "right"),
+    (KeybdKey::LeftKey,       "left"),
+    (KeybdKey::UpKey,         "up"),
+    (KeybdKey::DownKey,       "down"),
+    (KeybdKey::F11Key,        "f11"),
+    (KeybdKey::F12Key,        "f12"),
+    (KeybdKey::LAltKey,       "alt"),
+    (KeybdKey::RAltKey,       "alt"),
+    (KeybdKey::LSuper,        "super"),
+    (KeybdKey::RSuper,        "super"),
+    (KeybdKey::PageUpKey,     "pageup"),
+    (KeybdKey::PageDownKey,   "pagedown"),
+    (KeybdKey::HomeKey,       "home"),
+    (KeybdKey::EndKey,        "end"),
+    (KeybdKey::InsertKey,     "insert"),
+    (KeybdKey::DeleteKey,     "delete"),
+    (KeybdKey::NumLockKey,    "numlock"),
+    (KeybdKey::ScrollLockKey, "scrolllock"),
+];
+
+pub fn get_key_name(key: KeybdKey) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(named_key, _)| *named_key == key).map(|(_, name)| *name)
+}
+
+// Also takes a few common alternative names, and any character inputbot has a key for
+pub fn get_named_key(name: &str) -> Option<KeybdKey> {
+    let name: String = name.to_lowercase();
+    let name: &str = match name.as_str() {
+        "return" => "enter",
+        "esc" => "escape",
+        "ctrl" => "control",
+        "del" => "delete",
+        "meta" | "win" | "cmd" => "super",
+        "pgup" => "pageup",
+        "pgdn" => "pagedown",
+        name => name
+    };
+    if let Some((key, _)) = KEY_NAMES.iter().find(|(_, key_name)| *key_name == name) {
+        return Some(*key);
+    }
+
+    let mut characters = name.chars();
+    match (characters.next(), characters.next()) {
+        (Some(character), None) => get_keybd_key(character),
+        _ => None
+    }
+}
+
+// Parses a combination like "ctrl+shift+t", where the last key is the one tapped and the rest are held while it is
+pub fn parse_hotkey(hotkey: &str) -> Result<Vec<KeybdKey>, String> {
+    let keys: Vec<KeybdKey> = hotkey.split('+')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| get_named_key(name).ok_or(format!("Unknown key \"{}\" in \"{}\"", name, hotkey)))
+        .collect::<Result<Vec<KeybdKey>, String>>()?;
+
+    if keys.is_empty() {
+        return Err("No keys to press".to_string());
+    }
+    Ok(keys)
+}
+
+// The names mouse buttons have in the macro config, which mouse initiators match against too
+static BUTTON_NAMES: [(MouseButton, &str); 5] = [
+    (MouseButton::LeftButton,   "LMB"),
+    (MouseButton::RightButton,  "RMB"),
+    (MouseButton::MiddleButton, "MMB"),
+    (MouseButton::X1Button,     "X1"),
+    (MouseButton::X2Button,     "X2"),
+];
+
+pub fn get_button_name(button: MouseButton) -> Option<&'static str> {
+    BUTTON_NAMES.iter().find(|(named_button, _)| *named_button == button).map(|(_, name)| *name)
+}
+
+pub fn get_named_button(name: &str) -> Option<MouseButton> {
+    BUTTON_NAMES.iter().find(|(_, button_name)| button_name.eq_ignore_ascii_case(name)).map(|(button, _)| *button)
+}
+
+// Presses the keys in order and releases them in reverse, like a shortcut
+pub fn tap_keys<B: InputBackend + ?Sized>(backend: &mut B, keys: &[KeybdKey]) {
+    for key in keys {
+        backend.press_key(*key);
+    }
+    for key in keys.iter().rev() {
+        backend.release_key(*key);
+    }
+}
+
+// Types a character with the keys inputbot knows about. Returns false if there isn't a key for it.
+pub fn tap_character<B: InputBackend + ?Sized>(backend: &mut B, character: char) -> bool {
+    let key: KeybdKey = match character {
+        '\n' => KeybdKey::EnterKey,
+        '\t' => KeybdKey::TabKey,
+        ' ' => KeybdKey::SpaceKey,
+        _ if character.is_ascii() => match get_keybd_key(character) {
+            Some(key) => key,
+            None => return false
+        },
+        _ => return false
+    };
+
+    if needs_shift(character) {
+        tap_keys(backend, &[KeybdKey::LShiftKey, key]);
+    } else {
+        tap_keys(backend, &[key]);
+    }
+    true
+}
@@ -0,0 +1,285 @@
+use serde::{ Deserialize, Serialize };
+
+use std::fs::{ self, OpenOptions };
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use tauri::Manager;
+
+use super::get_app_handle;
+use super::running::get_timestamp;
+use super::paths::{ get_log_dir, get_old_log_path };
+
+// Each macro has its own log file. Once it gets too big it's moved aside, and only a few old files are kept.
+static MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+static MAX_OLD_LOG_FILES: usize = 3;
+static MAX_MESSAGE_LENGTH: usize = 10000;
+
+// Before each macro had its own log, every message went into one text file. Its messages don't have a macro name,
+// and their file can't have the same name as a macro's since those always end in a hash.
+static OLD_LOG_FILE_NAME: &str = "old-log";
+
+// Writing and rotating have to happen one at a time, or entries could end up in a file that's being moved
+static LOG_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    // Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(rename = "macro")]
+    pub macro_name: String,
+    // "info", "warning" or "error"
+    pub level: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    // Newest entries come first
+    pub entries: Vec<LogEntry>,
+    pub total: usize,
+}
+
+// Macro names can have characters that aren't allowed in file names. Names that clean up the same way, or that
+// only differ in case, still need files of their own, so a hash of the exact name goes on the end.
+fn get_log_file_name(macro_name: &str) -> String {
+    let cleaned: String = macro_name.chars()
+        .map(|character| if character.is_alphanumeric() || [' ', '-', '_'].contains(&character) { character } else { '_' })
+        .collect();
+    // FNV-1a, since the hash has to stay the same between versions of Rust
+    let hash: u64 = macro_name.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{}-{:08x}", cleaned, hash as u32)
+}
+
+// 0 is the current file, and higher numbers are older
+fn get_log_file(folder: &Path, file_name: &str, age: usize) -> PathBuf {
+    if age == 0 {
+        folder.join(format!("{}.jsonl", file_name))
+    } else {
+        folder.join(format!("{}.{}.jsonl", file_name, age))
+    }
+}
+
+pub fn write_log_entry(macro_name: &str, level: &str, message: &str) {
+    let folder: PathBuf = match get_log_dir() {
+        Some(folder) => folder,
+        None => return
+    };
+    let file_name: String = get_log_file_name(macro_name);
+
+    let entry: LogEntry = LogEntry {
+        timestamp: get_timestamp(),
+        macro_name: macro_name.to_string(),
+        level: level.to_string(),
+        message: message.chars().take(MAX_MESSAGE_LENGTH).collect(),
+    };
+    let line: String = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(_) => return
+    };
+
+    let lock = LOG_LOCK.lock().unwrap();
+    if let Err(error) = fs::create_dir_all(&folder) {
+        println!("Failed to create the log folder: {}", error);
+        return;
+    }
+
+    let path: PathBuf = get_log_file(&folder, &file_name, 0);
+    if fs::metadata(&path).map(|metadata| metadata.len() >= MAX_LOG_FILE_SIZE).unwrap_or(false) {
+        rotate_log_files(&folder, &file_name);
+    }
+
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(error) = result {
+        println!("Failed to write to the log: {}", error);
+    }
+    drop(lock);
+
+    // The log page shows new entries as they come in
+    if let Some(app_handle) = get_app_handle() {
+        let _ = app_handle.emit_all("log-entry", entry);
+    }
+}
+
+// Moves the messages from the old text log into a log of their own, so they still show up on the log page.
+// The text file is renamed afterwards, so this only happens once.
+pub fn import_old_log() {
+    if let (Some(folder), Some(old_path)) = (get_log_dir(), get_old_log_path()) {
+        import_log_file(&old_path, &folder);
+    }
+}
+
+fn import_log_file(old_path: &Path, folder: &Path) {
+    let content: String = match fs::read_to_string(old_path) {
+        Ok(content) => content,
+        Err(_) => return
+    };
+
+    // The old log didn't record when messages were written, so they all get the time the file was last changed
+    let timestamp: u64 = fs::metadata(old_path).and_then(|metadata| metadata.modified()).ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|modified| modified.as_millis() as u64)
+        .unwrap_or(0);
+
+    let mut lines: String = String::new();
+    for message in content.lines().filter(|message| !message.trim().is_empty()) {
+        let entry: LogEntry = LogEntry {
+            timestamp,
+            macro_name: String::new(),
+            level: "info".to_string(),
+            message: message.chars().take(MAX_MESSAGE_LENGTH).collect(),
+        };
+        lines.push_str(&serde_json::to_string(&entry).unwrap_or_default());
+        lines.push('\n');
+    }
+
+    let _lock = LOG_LOCK.lock().unwrap();
+    let result = fs::create_dir_all(folder)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(get_log_file(folder, OLD_LOG_FILE_NAME, 0)))
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .and_then(|_| fs::rename(old_path, old_path.with_extension("txt.imported")));
+    if let Err(error) = result {
+        println!("Failed to import the old log: {}", error);
+    }
+}
+
+fn rotate_log_files(folder: &Path, file_name: &str) {
+    let _ = fs::remove_file(get_log_file(folder, file_name, MAX_OLD_LOG_FILES));
+    for age in (0..MAX_OLD_LOG_FILES).rev() {
+        let path: PathBuf = get_log_file(folder, file_name, age);
+        if path.exists() {
+            let _ = fs::rename(&path, get_log_file(folder, file_name, age + 1));
+        }
+    }
+}
+
+pub fn clear_log(macro_name: &str) {
+    let folder: PathBuf = match get_log_dir() {
+        Some(folder) => folder,
+        None => return
+    };
+    let file_name: String = get_log_file_name(macro_name);
+
+    {
+        let _lock = LOG_LOCK.lock().unwrap();
+        for age in 0..=MAX_OLD_LOG_FILES {
+            let _ = fs::remove_file(get_log_file(&folder, &file_name, age));
+        }
+    }
+
+    if let Some(app_handle) = get_app_handle() {
+        let _ = app_handle.emit_all("log-cleared", macro_name.to_string());
+    }
+}
+
+// Pages through the entries of one macro, or of every macro if no name is given
+pub fn get_log_entries(macro_name: Option<&str>, offset: usize, limit: usize) -> LogPage {
+    let folder: PathBuf = match get_log_dir() {
+        Some(folder) => folder,
+        None => return LogPage { entries: Vec::new(), total: 0 }
+    };
+
+    let _lock = LOG_LOCK.lock().unwrap();
+    let mut entries: Vec<LogEntry> = Vec::new();
+    match macro_name {
+        Some(macro_name) => {
+            let file_name: String = get_log_file_name(macro_name);
+            for age in (0..=MAX_OLD_LOG_FILES).rev() {
+                read_log_file(&get_log_file(&folder, &file_name, age), &mut entries);
+            }
+            // Different macros can have the same file name once they're cleaned up
+            entries.retain(|entry| entry.macro_name == macro_name);
+        }
+        None => {
+            for file in fs::read_dir(&folder).into_iter().flatten().flatten() {
+                read_log_file(&file.path(), &mut entries);
+            }
+            entries.sort_by_key(|entry| entry.timestamp);
+        }
+    }
+
+    let total: usize = entries.len();
+    LogPage {
+        entries: entries.into_iter().rev().skip(offset).take(limit).collect(),
+        total,
+    }
+}
+
+fn read_log_file(path: &Path, entries: &mut Vec<LogEntry>) {
+    if path.extension().and_then(|extension| extension.to_str()) != Some("jsonl") {
+        return;
+    }
+    if let Ok(content) = fs::read_to_string(path) {
+        entries.extend(content.lines().filter_map(|line| serde_json::from_str::<LogEntry>(line).ok()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::paths::use_test_data_dir;
+
+    fn get_messages(macro_name: &str) -> Vec<String> {
+        get_log_entries(Some(macro_name), 0, 100).entries.into_iter().map(|entry| entry.message).collect()
+    }
+
+    #[test]
+    fn keeps_similar_names_apart() {
+        use_test_data_dir();
+        let names: [&str; 3] = ["Log test a/b", "Log test a?b", "Log test A?b"];
+        for name in names {
+            write_log_entry(name, "info", &format!("From {}", name));
+        }
+        assert_eq!(get_log_file_name(names[0]).len(), get_log_file_name(names[1]).len());
+        assert_ne!(get_log_file_name(names[0]), get_log_file_name(names[1]));
+
+        clear_log(names[0]);
+        assert!(get_messages(names[0]).is_empty());
+        assert_eq!(get_messages(names[1]), vec![format!("From {}", names[1])]);
+        assert_eq!(get_messages(names[2]), vec![format!("From {}", names[2])]);
+    }
+
+    #[test]
+    fn pages_newest_first() {
+        use_test_data_dir();
+        let name: &str = "Log test pages";
+        for index in 0..5 {
+            write_log_entry(name, "info", &index.to_string());
+        }
+
+        let page: LogPage = get_log_entries(Some(name), 1, 2);
+        assert_eq!(page.total, 5);
+        assert_eq!(page.entries.iter().map(|entry| entry.message.as_str()).collect::<Vec<&str>>(), vec!["3", "2"]);
+    }
+
+    #[test]
+    fn imports_the_old_log_once() {
+        use_test_data_dir();
+        let folder: PathBuf = std::env::temp_dir().join(format!("codemacros-log-test-{}", std::process::id()));
+        let old_path: PathBuf = folder.join("log.txt");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(&old_path, "First\n\nSecond\n").unwrap();
+
+        import_log_file(&old_path, &folder.join("Logs"));
+        import_log_file(&old_path, &folder.join("Logs"));
+        assert!(!old_path.exists());
+        assert!(old_path.with_extension("txt.imported").exists());
+
+        let mut entries: Vec<LogEntry> = Vec::new();
+        read_log_file(&get_log_file(&folder.join("Logs"), OLD_LOG_FILE_NAME, 0), &mut entries);
+        assert_eq!(entries.iter().map(|entry| entry.message.as_str()).collect::<Vec<&str>>(), vec!["First", "Second"]);
+        assert!(entries.iter().all(|entry| entry.macro_name.is_empty()));
+
+        // A macro that happens to be called "Old log" has its own file
+        assert_ne!(get_log_file_name("Old log"), OLD_LOG_FILE_NAME);
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -1,23 +1,61 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
 
-use tauri::{ CustomMenuItem, SystemTray, SystemTrayMenu, SystemTrayMenuItem, SystemTrayEvent, AppHandle, Manager };
+use tauri::{ SystemTray, AppHandle, Manager };
 
 use std::thread;
 
 use std::sync::{ Mutex };
+use std::sync::atomic::{ AtomicBool, Ordering };
 use once_cell::sync::Lazy;
 
 static MACROS: Lazy<Mutex<Macros>> = Lazy::new(|| Mutex::new(Macros::new()));
 static APPHANDLE: Lazy<Mutex<Option<AppHandle>>> = Lazy::new(|| Mutex::new(None));
+// When paused, no initiators run, but macros can still be run by hand
+static PAUSED: AtomicBool = AtomicBool::new(false);
 
 mod initiators;
 mod execute;
+mod running;
+mod settings;
+mod ipc;
+mod clipboard;
+mod functions;
+mod tray;
+mod dryrun;
+mod debugger;
+mod history;
+mod log;
+mod paths;
+mod keys;
+mod typing;
+mod input;
+mod screen;
+#[cfg(target_os = "linux")]
+mod uinput;
 
 use initiators::*;
 use initiators::keypress::*;
 use initiators::cron::*;
+use initiators::schedule::*;
+use initiators::process::*;
+use initiators::filewatch::*;
+use initiators::webhook::*;
+use initiators::lifecycle::*;
+use initiators::clipboard::*;
+use initiators::mouse::*;
 
 use execute::*;
+use running::*;
+use settings::*;
+use clipboard::*;
+use functions::*;
+use tray::*;
+use dryrun::*;
+use debugger::*;
+use history::*;
+use log::*;
+use paths::*;
+use ipc::*;
 
 fn get_macros() -> Macros {
     MACROS.lock().unwrap().clone()
@@ -27,6 +65,17 @@ fn set_macros(macros: Macros) {
     *MACROS.lock().unwrap() = macros;
 }
 
+// Tests run in parallel, so the ones that need macros hold this until they're done with them
+#[cfg(test)]
+static TEST_MACROS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[cfg(test)]
+fn use_test_macros(macros: Macros) -> std::sync::MutexGuard<'static, ()> {
+    let guard = TEST_MACROS_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    set_macros(macros);
+    guard
+}
+
 fn get_app_handle() -> Option<AppHandle> {
     APPHANDLE.lock().unwrap().clone()
 }
@@ -35,53 +84,123 @@ fn set_app_handle(app_handle: AppHandle) {
     *APPHANDLE.lock().unwrap() = Some(app_handle);
 }
 
+fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+fn set_paused_state(paused: bool) {
+    PAUSED.store(paused, Ordering::SeqCst);
+    update_tray_menu();
+    if let Some(app_handle) = get_app_handle() {
+        let _ = app_handle.emit_all("paused-changed", paused);
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EnabledChanged {
+    macro_name: String,
+    initiator: Option<usize>,
+    enabled: bool,
+}
+
+// Enables or disables a whole macro, or one of its initiators if an index is given.
+// The frontend is told about the change so it can save it, since it might not have made it (like from the tray).
+fn set_enabled_state(macro_name: &str, initiator: Option<usize>, enabled: bool) -> Result<(), String> {
+    {
+        let mut macros = MACROS.lock().unwrap();
+        let macro_: &mut Macro = macros.iter_mut()
+            .find(|macro_| macro_.name == macro_name)
+            .ok_or(format!("There is no macro named \"{}\"", macro_name))?;
+        match initiator {
+            Some(index) => {
+                macro_.macro_.initiators.as_mut()
+                    .and_then(|initiators| initiators.get_mut(index))
+                    .ok_or(format!("Macro \"{}\" has no initiator {}", macro_name, index))?
+                    .enabled = enabled;
+            }
+            None => macro_.enabled = enabled
+        }
+    }
+
+    reload_initiators();
+    update_tray_menu();
+    if let Some(app_handle) = get_app_handle() {
+        let _ = app_handle.emit_all("enabled-changed", EnabledChanged {
+            macro_name: macro_name.to_string(),
+            initiator,
+            enabled,
+        });
+    }
+    Ok(())
+}
+
+// Initiators that keep their own copy of the macros need to be set up again when the macros change
+fn reload_initiators() {
+    let check_missed: bool = take_missed_check();
+    listen_initiator_cron(check_missed);
+    listen_initiator_schedule(check_missed);
+    listen_initiator_filewatch();
+}
+
 fn main() {
+    // Commands like "codemacros trigger <macro>" are sent to the instance that's already running
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    match take_data_dir_arg(&mut args) {
+        Ok(Some(data_dir)) => set_data_dir_override(data_dir),
+        Ok(None) => {}
+        Err(error) => {
+            attach_console();
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    }
+    if let Some(exit_code) = run_cli_command(&args) {
+        std::process::exit(exit_code);
+    }
+    import_old_log();
+
+    listen_initiator_mouse();
     thread::spawn(move || {
         listen_initiator_keypress();
     });
 
+    thread::spawn(move || {
+        listen_initiator_process();
+    });
+
+    thread::spawn(move || {
+        listen_initiator_lifecycle();
+    });
+
+    thread::spawn(move || {
+        listen_initiator_clipboard();
+    });
+
     tauri::Builder
         ::default()
         .plugin(
-            tauri_plugin_single_instance::init(|app, argv, cwd| {
-                println!("{}, {argv:?}, {cwd}", app.package_info().name);
+            // Commands like "codemacros trigger" are sent over the IPC socket before this instance would start,
+            // so launching it again only has to show the window
+            tauri_plugin_single_instance::init(|app, _argv, _cwd| {
                 let window = app.get_window("main").unwrap();
                 window.show().unwrap();
             })
         )
         .setup(|app| {
             set_app_handle(app.handle());
+            set_clipboard_backend(Box::new(TauriClipboard));
+            mark_app_started();
+
+            thread::spawn(move || {
+                listen_ipc();
+            });
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![update_macros])
-        .system_tray(
-            SystemTray::new().with_menu(
-                SystemTrayMenu::new()
-                    .add_item(CustomMenuItem::new("open".to_string(), "Open"))
-                    .add_native_item(SystemTrayMenuItem::Separator)
-                    .add_item(CustomMenuItem::new("quit".to_string(), "Quit"))
-            )
-        )
-        .on_system_tray_event(|app, event| {
-            match event {
-                SystemTrayEvent::MenuItemClick { id, .. } => {
-                    // let item_handle = app.tray_handle().get_item(&id);
-                    match id.as_str() {
-                        "quit" => {
-                            let window = app.get_window("main").unwrap();
-                            window.close().unwrap();
-                        }
-                        "open" => {
-                            let window = app.get_window("main").unwrap();
-                            window.show().unwrap();
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
-        })
+        .invoke_handler(tauri::generate_handler![update_macros, update_settings, get_running, stop_running, pause_macros, get_paused, release_inputs, set_macro_enabled, set_initiator_enabled, run_macro, debug_macro, debug_command, set_breakpoints, get_history, clear_history, get_log, clear_macro_log, get_paths])
+        .system_tray(SystemTray::new().with_menu(get_tray_menu()))
+        .on_system_tray_event(handle_tray_event)
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -95,6 +214,8 @@ pub struct Macro {
     pub name: String,
     pub description: String,
     pub macro_: MacroMacro,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -103,9 +224,136 @@ pub struct MacroMacro {
     pub functions: Option<Vec<Function>>,
 }
 
+// Returns problems found in the macros, like calls to functions that don't exist
 #[tauri::command]
-fn update_macros(macros: Macros) {
+fn update_macros(macros: Macros) -> Vec<String> {
     println!("Updating macros");
+    let problems: Vec<String> = validate_function_calls(&macros);
+    for problem in &problems {
+        println!("{}", problem);
+    }
     set_macros(macros);
-    listen_initiator_cron();
-}
\ No newline at end of file
+    reload_initiators();
+    update_tray_menu();
+    listen_initiator_startup();
+
+    problems
+}
+
+#[tauri::command]
+fn update_settings(settings: Settings) {
+    println!("Updating settings");
+    set_settings(settings);
+    listen_initiator_webhook();
+    listen_initiator_evdev();
+}
+
+#[tauri::command]
+fn get_running() -> Vec<RunningMacro> {
+    get_running_macros()
+}
+
+#[tauri::command]
+fn stop_running(id: u64) -> bool {
+    stop_run(id)
+}
+
+// Lets go of keys and mouse buttons that are stuck down, like after a macro was stopped between pressing and releasing them
+#[tauri::command]
+fn release_inputs() {
+    release_all_inputs();
+}
+
+#[tauri::command]
+fn pause_macros(paused: bool) {
+    set_paused_state(paused);
+}
+
+#[tauri::command]
+fn get_paused() -> bool {
+    is_paused()
+}
+
+#[tauri::command]
+fn set_macro_enabled(macro_name: String, enabled: bool) -> Result<(), String> {
+    set_enabled_state(&macro_name, None, enabled)
+}
+
+#[tauri::command]
+fn set_initiator_enabled(macro_name: String, initiator: usize, enabled: bool) -> Result<(), String> {
+    set_enabled_state(&macro_name, Some(initiator), enabled)
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ManualRun {
+    id: u64,
+    // Dry runs finish before the command returns, so their result is included
+    dry_run: Option<DryRun>,
+}
+
+// Runs a macro by hand, even if it's disabled or macros are paused
+#[tauri::command]
+async fn run_macro(macro_name: String, initiator: usize, variables: Option<serde_json::Value>, dry_run: bool) -> Result<ManualRun, String> {
+    let (macro_, initiator_data) = find_macro_initiator(&macro_name, initiator)?;
+    let variables: Variables = get_json_variables(&variables.unwrap_or(serde_json::Value::Null))?;
+
+    if dry_run {
+        // Dry runs execute on this thread until they finish, so they're kept off the async runtime
+        let (id, dry_run) = tauri::async_runtime::spawn_blocking(move || dry_run_macro(initiator_data, macro_, variables))
+            .await
+            .map_err(|error| format!("The dry run failed: {}", error))?;
+        Ok(ManualRun { id, dry_run: Some(dry_run) })
+    } else {
+        let id: u64 = start_macro_run(initiator_data, initiator, macro_, variables, "manual".to_string());
+        Ok(ManualRun { id, dry_run: None })
+    }
+}
+
+#[tauri::command]
+fn debug_macro(macro_name: String, initiator: usize, variables: Option<serde_json::Value>, breakpoints: Vec<String>) -> Result<u64, String> {
+    let (macro_, initiator_data) = find_macro_initiator(&macro_name, initiator)?;
+    let variables: Variables = get_json_variables(&variables.unwrap_or(serde_json::Value::Null))?;
+    Ok(start_debug_run(initiator_data, initiator, macro_, variables, breakpoints))
+}
+
+#[tauri::command]
+fn debug_command(id: u64, command: String) -> Result<(), String> {
+    let command: DebugCommand = match command.as_str() {
+        "step" => DebugCommand::Step,
+        "continue" => DebugCommand::Continue,
+        "stop" => DebugCommand::Stop,
+        _ => return Err(format!("Unknown debug command \"{}\"", command))
+    };
+    send_debug_command(id, command)
+}
+
+#[tauri::command]
+fn set_breakpoints(id: u64, breakpoints: Vec<String>) -> Result<(), String> {
+    set_debug_breakpoints(id, breakpoints)
+}
+
+#[tauri::command]
+fn get_history(macro_name: Option<String>, limit: Option<usize>) -> Vec<RunRecord> {
+    get_run_history(macro_name.as_deref(), limit)
+}
+
+#[tauri::command]
+fn clear_history() {
+    clear_run_history();
+}
+
+#[tauri::command]
+fn get_log(macro_name: Option<String>, offset: usize, limit: usize) -> LogPage {
+    get_log_entries(macro_name.as_deref(), offset, limit)
+}
+
+#[tauri::command]
+fn clear_macro_log(macro_name: String) {
+    clear_log(&macro_name);
+}
+
+#[tauri::command]
+fn get_paths() -> Result<DataPaths, String> {
+    get_data_paths().ok_or("Couldn't find a folder to keep data in".to_string())
+}
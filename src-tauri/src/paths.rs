@@ -0,0 +1,186 @@
+use serde::Serialize;
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+// Every file the app keeps is in one data folder, which is "CodeMacros" in the user's config folder.
+// It can be moved with the --data-dir flag or this environment variable, like for portable installs or testing.
+pub static DATA_DIR_ENV: &str = "CODEMACROS_DATA_DIR";
+static DATA_DIR_FLAG: &str = "--data-dir";
+
+static DATA_DIR_OVERRIDE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DataPaths {
+    pub data_dir: PathBuf,
+    pub config: PathBuf,
+    pub logs: PathBuf,
+}
+
+pub fn set_data_dir_override(path: PathBuf) {
+    *DATA_DIR_OVERRIDE.lock().unwrap() = Some(path);
+}
+
+// Keeps tests from touching the real data folder
+#[cfg(test)]
+pub fn use_test_data_dir() {
+    set_data_dir_override(std::env::temp_dir().join(format!("codemacros-test-{}", std::process::id())));
+}
+
+// Takes "--data-dir <path>" or "--data-dir=<path>" out of the arguments, so the rest can be handled like normal
+pub fn take_data_dir_arg(args: &mut Vec<String>) -> Result<Option<PathBuf>, String> {
+    let position: usize = match args.iter().position(|arg| arg == DATA_DIR_FLAG || arg.starts_with(&format!("{}=", DATA_DIR_FLAG))) {
+        Some(position) => position,
+        None => return Ok(None)
+    };
+
+    let arg: String = args.remove(position);
+    let value: String = match arg.split_once('=') {
+        Some((_, value)) => value.to_string(),
+        None if position < args.len() => args.remove(position),
+        None => return Err(format!("{} needs a folder", DATA_DIR_FLAG))
+    };
+    if value.is_empty() {
+        return Err(format!("{} needs a folder", DATA_DIR_FLAG));
+    }
+    Ok(Some(get_absolute_path(PathBuf::from(value))))
+}
+
+// The frontend reads files by absolute path, so relative folders are resolved from where the app was started
+fn get_absolute_path(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        Err(_) => path
+    }
+}
+
+pub fn get_data_dir() -> Option<PathBuf> {
+    choose_data_dir(DATA_DIR_OVERRIDE.lock().unwrap().clone(), std::env::var_os(DATA_DIR_ENV), tauri::api::path::config_dir())
+}
+
+// The --data-dir flag comes first, then the environment variable, then the config folder
+fn choose_data_dir(flag: Option<PathBuf>, env: Option<OsString>, config_dir: Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(path) = flag {
+        return Some(path);
+    }
+    if let Some(path) = env.filter(|path| !path.is_empty()) {
+        return Some(get_absolute_path(PathBuf::from(path)));
+    }
+    Some(config_dir?.join("CodeMacros"))
+}
+
+// The macros and settings saved by the frontend
+pub fn get_config_path() -> Option<PathBuf> {
+    Some(get_data_dir()?.join("config.json"))
+}
+
+pub fn get_log_dir() -> Option<PathBuf> {
+    Some(get_data_dir()?.join("Logs"))
+}
+
+pub fn get_history_path() -> Option<PathBuf> {
+    Some(get_data_dir()?.join("history.jsonl"))
+}
+
+pub fn get_old_log_path() -> Option<PathBuf> {
+    choose_old_log_path(tauri::api::path::config_dir(), tauri::api::path::home_dir())
+}
+
+// Before there was a data folder, the one log file was kept two folders up from the app's log folder, in "CodeMacros".
+// On macOS that's the Library folder, and everywhere else it's the config folder.
+fn choose_old_log_path(config_dir: Option<PathBuf>, home_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let parent: PathBuf = if cfg!(target_os = "macos") { home_dir?.join("Library") } else { config_dir? };
+    Some(parent.join("CodeMacros").join("Logs").join("log.txt"))
+}
+
+// Files the backend uses to remember things between launches, like when scheduled initiators last ran
+pub fn get_state_path(name: &str) -> Option<PathBuf> {
+    Some(get_data_dir()?.join(name))
+}
+
+pub fn get_data_paths() -> Option<DataPaths> {
+    Some(DataPaths {
+        data_dir: get_data_dir()?,
+        config: get_config_path()?,
+        logs: get_log_dir()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn takes_the_data_dir_flag() {
+        let folder: PathBuf = std::env::temp_dir().join("macros");
+        let folder_arg: String = folder.to_string_lossy().to_string();
+
+        let mut arguments: Vec<String> = args(&["trigger", "--data-dir", &folder_arg, "My macro"]);
+        assert_eq!(take_data_dir_arg(&mut arguments), Ok(Some(folder.clone())));
+        assert_eq!(arguments, args(&["trigger", "My macro"]));
+
+        let mut arguments: Vec<String> = args(&[&format!("--data-dir={}", folder_arg), "list"]);
+        assert_eq!(take_data_dir_arg(&mut arguments), Ok(Some(folder)));
+        assert_eq!(arguments, args(&["list"]));
+
+        let mut arguments: Vec<String> = args(&["list"]);
+        assert_eq!(take_data_dir_arg(&mut arguments), Ok(None));
+        assert_eq!(arguments, args(&["list"]));
+
+        assert!(take_data_dir_arg(&mut args(&["--data-dir"])).is_err());
+        assert!(take_data_dir_arg(&mut args(&["--data-dir="])).is_err());
+    }
+
+    #[test]
+    fn resolves_relative_data_dirs() {
+        let current_dir: PathBuf = std::env::current_dir().unwrap();
+        assert_eq!(take_data_dir_arg(&mut args(&["--data-dir", "portable"])), Ok(Some(current_dir.join("portable"))));
+        assert_eq!(choose_data_dir(None, Some(OsString::from("portable")), None), Some(current_dir.join("portable")));
+    }
+
+    #[test]
+    fn chooses_the_data_dir() {
+        let root: PathBuf = std::env::temp_dir();
+        let flag: Option<PathBuf> = Some(root.join("flag"));
+        let env: Option<OsString> = Some(root.join("env").into_os_string());
+        let config_dir: Option<PathBuf> = Some(root.join("config"));
+
+        assert_eq!(choose_data_dir(flag.clone(), env.clone(), config_dir.clone()), Some(root.join("flag")));
+        assert_eq!(choose_data_dir(None, env, config_dir.clone()), Some(root.join("env")));
+        assert_eq!(choose_data_dir(None, Some(OsString::new()), config_dir.clone()), Some(root.join("config").join("CodeMacros")));
+        assert_eq!(choose_data_dir(None, None, config_dir), Some(root.join("config").join("CodeMacros")));
+        assert_eq!(choose_data_dir(None, None, None), None);
+    }
+
+    #[test]
+    fn keeps_everything_in_the_data_dir() {
+        use_test_data_dir();
+        let data_dir: PathBuf = get_data_dir().unwrap();
+        let paths: DataPaths = get_data_paths().unwrap();
+
+        assert_eq!(paths.data_dir, data_dir);
+        assert_eq!(paths.config, data_dir.join("config.json"));
+        assert_eq!(paths.logs, data_dir.join("Logs"));
+        assert_eq!(get_history_path(), Some(data_dir.join("history.jsonl")));
+        assert_eq!(get_state_path("last_runs.json"), Some(data_dir.join("last_runs.json")));
+    }
+
+    #[test]
+    fn finds_the_old_log() {
+        let root: PathBuf = std::env::temp_dir();
+        let old_log: Option<PathBuf> = choose_old_log_path(Some(root.join("config")), Some(root.join("home")));
+        let parent: PathBuf = if cfg!(target_os = "macos") { root.join("home").join("Library") } else { root.join("config") };
+        assert_eq!(old_log, Some(parent.join("CodeMacros").join("Logs").join("log.txt")));
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, MutexGuard };
+use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
+use std::thread;
+use std::time::{ Duration, Instant, SystemTime, UNIX_EPOCH };
+
+use once_cell::sync::Lazy;
+
+use super::tray::update_tray_running;
+use super::dryrun::DryRunAction;
+use super::debugger::Debugger;
+use super::history::{ get_last_run_id, TraceEntry };
+use super::input::{ InputBackend, TrackedInput, create_input_backend, release_stuck_inputs };
+
+// Every macro run gets an ID so it can be referred to from outside the thread running it.
+// They carry on from the run history, so IDs in it stay unique across launches.
+static NEXT_RUN_ID: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(get_last_run_id() + 1));
+static RUNNING: Lazy<Mutex<HashMap<u64, RunningMacro>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningMacro {
+    pub id: u64,
+    pub macro_name: String,
+    pub trigger: String,
+    pub started: u64,
+    #[serde(skip)]
+    stop_requested: Arc<AtomicBool>,
+    #[serde(skip)]
+    input: Arc<Mutex<TrackedInput>>,
+}
+
+// The state of one macro run, passed through the code it executes.
+// Dropping it removes the run from the registry, so runs that panic are cleaned up too.
+pub struct MacroRun {
+    pub id: u64,
+    pub macro_name: String,
+    pub trigger: String,
+    pub started: u64,
+    // Set by the stop execution
+    pub stop_execution: bool,
+    // How many macros started this one by chaining, so macros that start each other can't loop forever
+    pub depth: u32,
+    // In a dry run, executions with side effects are recorded here instead of being done
+    pub dry_run: Option<Vec<DryRunAction>>,
+    pub debugger: Option<Debugger>,
+    // Where the execution being run is, like ["initiator:0", "2", "then", "1"]
+    pub path: Vec<String>,
+    // Each execution and how long it took, if tracing is turned on
+    pub trace: Option<Vec<TraceEntry>>,
+    stop_requested: Arc<AtomicBool>,
+    // Keyboard and mouse input goes through here, so anything held is released when the run ends however it ends
+    input: Arc<Mutex<TrackedInput>>,
+}
+
+impl MacroRun {
+    pub fn should_stop(&self) -> bool {
+        self.stop_execution || self.stop_requested()
+    }
+
+    // Whether the run was stopped from outside, rather than by its own code
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested.load(Ordering::SeqCst)
+    }
+
+    // Waits in short steps so stopping a run doesn't have to wait for a long wait block to finish.
+    // Dry runs don't wait at all, the same as they skip wait blocks.
+    pub fn sleep(&self, duration: Duration) {
+        if self.dry_run.is_some() {
+            return;
+        }
+        let start: Instant = Instant::now();
+        while !self.should_stop() {
+            let elapsed: Duration = start.elapsed();
+            if elapsed >= duration {
+                break;
+            }
+            thread::sleep((duration - elapsed).min(Duration::from_millis(50)));
+        }
+    }
+
+    pub fn input(&self) -> MutexGuard<'_, TrackedInput> {
+        self.input.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    pub fn set_input_backend(&self, backend: Box<dyn InputBackend>) {
+        *self.input() = TrackedInput::new(backend);
+    }
+}
+
+impl Drop for MacroRun {
+    fn drop(&mut self) {
+        self.input().release_all();
+        RUNNING.lock().unwrap_or_else(|error| error.into_inner()).remove(&self.id);
+        update_tray_running();
+    }
+}
+
+pub fn start_run(macro_name: &str, trigger: &str) -> MacroRun {
+    let id: u64 = NEXT_RUN_ID.fetch_add(1, Ordering::SeqCst);
+    let stop_requested: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let started: u64 = get_timestamp();
+    let input: Arc<Mutex<TrackedInput>> = Arc::new(Mutex::new(TrackedInput::new(create_input_backend())));
+
+    RUNNING.lock().unwrap().insert(id, RunningMacro {
+        id,
+        macro_name: macro_name.to_string(),
+        trigger: trigger.to_string(),
+        started,
+        stop_requested: stop_requested.clone(),
+        input: input.clone(),
+    });
+    update_tray_running();
+
+    MacroRun {
+        id,
+        macro_name: macro_name.to_string(),
+        trigger: trigger.to_string(),
+        started,
+        stop_execution: false,
+        depth: 0,
+        dry_run: None,
+        debugger: None,
+        path: Vec::new(),
+        trace: None,
+        stop_requested,
+        input
+    }
+}
+
+// Asks a run to stop. It stops before its next block, so a block that's already running (like typing a string) finishes first.
+pub fn stop_run(id: u64) -> bool {
+    match RUNNING.lock().unwrap().get(&id) {
+        Some(run) => {
+            run.stop_requested.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false
+    }
+}
+
+pub fn is_stop_requested(id: u64) -> bool {
+    match RUNNING.lock().unwrap().get(&id) {
+        Some(run) => run.stop_requested.load(Ordering::SeqCst),
+        None => true
+    }
+}
+
+// For when something is stuck down: releases what every run is holding, and the modifiers and mouse buttons too
+pub fn release_all_inputs() {
+    let inputs: Vec<Arc<Mutex<TrackedInput>>> = RUNNING.lock().unwrap_or_else(|error| error.into_inner()).values().map(|run| run.input.clone()).collect();
+    for input in inputs {
+        input.lock().unwrap_or_else(|error| error.into_inner()).release_all();
+    }
+    release_stuck_inputs();
+}
+
+pub fn get_running_macros() -> Vec<RunningMacro> {
+    let mut running: Vec<RunningMacro> = RUNNING.lock().unwrap().values().cloned().collect();
+    running.sort_by_key(|run| run.id);
+    running
+}
+
+// Milliseconds since the unix epoch, which is what the frontend's Date uses
+pub fn get_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0)
+}
@@ -0,0 +1,46 @@
+use tauri::{ Manager, Monitor, Window };
+
+use super::get_app_handle;
+use super::input::InputBackend;
+
+// In physical pixels, the same as mouse positions
+pub struct ScreenBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// "primary" is the main monitor, and "mouse" is the one the mouse is on. The mouse position comes from the run's input,
+// so it's where uinput or a dry run thinks the mouse is.
+pub fn get_screen_bounds(monitor: &str, input: &dyn InputBackend) -> Result<ScreenBounds, String> {
+    // Monitors can only be listed through a window. The main one is always there, even while it's hidden.
+    let window: Window = get_app_handle()
+        .and_then(|app_handle| app_handle.get_window("main"))
+        .ok_or("Screens can't be found until the app has started")?;
+
+    let primary: Option<Monitor> = window.primary_monitor().map_err(|error| error.to_string())?;
+    let found: Option<Monitor> = match monitor {
+        "mouse" => {
+            let (x, y) = input.get_mouse_position()?;
+            let monitors: Vec<Monitor> = window.available_monitors().map_err(|error| error.to_string())?;
+            monitors.into_iter().find(|monitor| contains_point(monitor, x, y)).or(primary)
+        }
+        _ => primary
+    };
+
+    found
+        .map(|monitor| ScreenBounds {
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+        })
+        .ok_or(format!("Couldn't find the {} monitor", monitor))
+}
+
+fn contains_point(monitor: &Monitor, x: i32, y: i32) -> bool {
+    let position = monitor.position();
+    let size = monitor.size();
+    x >= position.x && y >= position.y && x < position.x + size.width as i32 && y < position.y + size.height as i32
+}
@@ -0,0 +1,32 @@
+use serde::{ Deserialize, Serialize };
+
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+
+static SETTINGS: Lazy<Mutex<Settings>> = Lazy::new(|| Mutex::new(Settings::default()));
+
+// Settings are stored by the frontend and sent here whenever they change, like macros are
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub webhook_enabled: bool,
+    pub webhook_address: Option<String>,
+    pub webhook_port: Option<u16>,
+    pub webhook_token: Option<String>,
+    // Record every execution and how long it took in the run history
+    pub trace_runs: bool,
+    // "uinput" sends input through /dev/uinput and reads hotkeys from /dev/input, which also works on Wayland. Anything else uses inputbot.
+    pub input_backend: String,
+}
+
+pub fn get_settings() -> Settings {
+    SETTINGS.lock().unwrap().clone()
+}
+
+pub fn set_settings(settings: Settings) {
+    *SETTINGS.lock().unwrap() = settings;
+}
+
+pub fn uses_uinput() -> bool {
+    cfg!(target_os = "linux") && SETTINGS.lock().unwrap().input_backend == "uinput"
+}
@@ -0,0 +1,168 @@
+use super::{ get_macros, get_app_handle, is_paused, set_paused_state, set_enabled_state, Macro };
+use super::execute::{ start_macro_run, Variables };
+use super::running::{ get_running_macros, stop_run, release_all_inputs, RunningMacro };
+
+use tauri::{ AppHandle, CustomMenuItem, Manager, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu };
+
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+// Initiators like intervals and file watchers can start runs many times a second, and rebuilding the menu closes it
+// if it's open. So changes to the running macros are batched, and the menu is only rebuilt if they're different.
+static RUNNING_UPDATE_PENDING: AtomicBool = AtomicBool::new(false);
+const RUNNING_UPDATE_DELAY: Duration = Duration::from_millis(500);
+// The IDs of the runs the menu was last built with
+static MENU_RUNNING_IDS: Lazy<Mutex<Vec<u64>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Macros and initiators are referred to by their index in the menu item IDs, like "initiator:2:0".
+// The menu is rebuilt whenever the macros or running macros change, so the indexes always match.
+pub fn get_tray_menu() -> SystemTrayMenu {
+    let macros: Vec<Macro> = get_macros();
+
+    let mut macros_menu: SystemTrayMenu = SystemTrayMenu::new();
+    if macros.is_empty() {
+        macros_menu = macros_menu.add_item(CustomMenuItem::new("none:macros".to_string(), "No macros").disabled());
+    }
+    for (index, macro_) in macros.iter().enumerate() {
+        let mut macro_menu: SystemTrayMenu = SystemTrayMenu::new()
+            .add_item(checked(CustomMenuItem::new(format!("macro:{}", index), "Enabled"), macro_.enabled))
+            .add_native_item(SystemTrayMenuItem::Separator);
+        for (initiator_index, initiator) in macro_.macro_.initiators.iter().flatten().enumerate() {
+            let initiator_menu: SystemTrayMenu = SystemTrayMenu::new()
+                .add_item(CustomMenuItem::new(format!("run:{}:{}", index, initiator_index), "Run now"))
+                .add_item(checked(CustomMenuItem::new(format!("initiator:{}:{}", index, initiator_index), "Enabled"), initiator.enabled));
+            macro_menu = macro_menu.add_submenu(SystemTraySubmenu::new(format!("{}: {}", initiator_index, initiator.type_), initiator_menu));
+        }
+        macros_menu = macros_menu.add_submenu(SystemTraySubmenu::new(macro_.name.clone(), macro_menu));
+    }
+
+    let running: Vec<RunningMacro> = get_running_macros();
+    *MENU_RUNNING_IDS.lock().unwrap() = get_running_ids(&running);
+    let mut running_menu: SystemTrayMenu = SystemTrayMenu::new();
+    if running.is_empty() {
+        running_menu = running_menu.add_item(CustomMenuItem::new("none:running".to_string(), "Nothing is running").disabled());
+    }
+    for run in &running {
+        running_menu = running_menu.add_item(CustomMenuItem::new(
+            format!("stop:{}", run.id),
+            format!("Stop {} ({}, run {})", run.macro_name, run.trigger, run.id)
+        ));
+    }
+
+    SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new("open".to_string(), "Open"))
+        .add_submenu(SystemTraySubmenu::new("Macros", macros_menu))
+        .add_submenu(SystemTraySubmenu::new(format!("Running ({})", running.len()), running_menu))
+        .add_item(checked(CustomMenuItem::new("pause".to_string(), "Pause all macros"), is_paused()))
+        .add_item(CustomMenuItem::new("release".to_string(), "Release all keys and buttons"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new("quit".to_string(), "Quit"))
+}
+
+fn checked(item: CustomMenuItem, selected: bool) -> CustomMenuItem {
+    if selected {
+        item.selected()
+    } else {
+        item
+    }
+}
+
+pub fn update_tray_menu() {
+    if let Some(app_handle) = get_app_handle() {
+        if let Err(error) = app_handle.tray_handle().set_menu(get_tray_menu()) {
+            println!("Failed to update the tray menu: {}", error);
+        }
+    }
+}
+
+// Called when a run starts or ends
+pub fn update_tray_running() {
+    if RUNNING_UPDATE_PENDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(|| {
+        thread::sleep(RUNNING_UPDATE_DELAY);
+        RUNNING_UPDATE_PENDING.store(false, Ordering::SeqCst);
+        if get_running_ids(&get_running_macros()) != *MENU_RUNNING_IDS.lock().unwrap() {
+            update_tray_menu();
+        }
+    });
+}
+
+fn get_running_ids(running: &[RunningMacro]) -> Vec<u64> {
+    running.iter().map(|run| run.id).collect()
+}
+
+pub fn handle_tray_event(app: &AppHandle, event: SystemTrayEvent) {
+    let id: String = match event {
+        SystemTrayEvent::MenuItemClick { id, .. } => id,
+        _ => return
+    };
+    let parts: Vec<&str> = id.split(':').collect();
+
+    let result: Result<(), String> = match parts.as_slice() {
+        ["open"] => {
+            let window = app.get_window("main").unwrap();
+            window.show().unwrap();
+            Ok(())
+        }
+        ["quit"] => {
+            app.exit(0);
+            Ok(())
+        }
+        ["pause"] => {
+            set_paused_state(!is_paused());
+            Ok(())
+        }
+        ["release"] => {
+            release_all_inputs();
+            Ok(())
+        }
+        ["macro", index] => match get_tray_macro(index) {
+            Some(macro_) => set_enabled_state(&macro_.name, None, !macro_.enabled),
+            None => Ok(())
+        },
+        ["initiator", index, initiator_index] => {
+            let initiator_index: usize = initiator_index.parse().unwrap_or(usize::MAX);
+            let enabled: Option<(String, bool)> = get_tray_macro(index).and_then(|macro_| {
+                let initiator = macro_.macro_.initiators.as_ref()?.get(initiator_index)?;
+                Some((macro_.name.clone(), initiator.enabled))
+            });
+            match enabled {
+                Some((macro_name, enabled)) => set_enabled_state(&macro_name, Some(initiator_index), !enabled),
+                None => Ok(())
+            }
+        }
+        ["run", index, initiator_index] => {
+            let initiator_index: usize = initiator_index.parse().unwrap_or(usize::MAX);
+            let found = get_tray_macro(index).and_then(|macro_| {
+                let initiator = macro_.macro_.initiators.as_ref()?.get(initiator_index)?.clone();
+                Some((macro_, initiator))
+            });
+            // Running by hand works even if the macro is disabled or paused
+            if let Some((macro_, initiator)) = found {
+                start_macro_run(initiator, initiator_index, macro_, Variables::new(), "tray".to_string());
+            }
+            Ok(())
+        }
+        ["stop", id] => {
+            if let Ok(id) = id.parse::<u64>() {
+                stop_run(id);
+            }
+            Ok(())
+        }
+        _ => Ok(())
+    };
+
+    if let Err(error) = result {
+        println!("{}", error);
+    }
+}
+
+fn get_tray_macro(index: &str) -> Option<Macro> {
+    get_macros().get(index.parse::<usize>().ok()?).cloned()
+}
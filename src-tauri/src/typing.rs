@@ -0,0 +1,287 @@
+use inputbot::KeybdKey;
+
+use std::time::Duration;
+
+use super::keys::{ parse_hotkey, tap_keys };
+use super::input::InputBackend;
+use super::running::MacroRun;
+
+// Types characters that aren't on the keyboard
+#[cfg(target_os = "windows")]
+pub use windows::UnicodeTyper;
+#[cfg(target_os = "linux")]
+pub use linux::UnicodeTyper;
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub use other::UnicodeTyper;
+
+pub enum TypingToken {
+    Character(char),
+    // Pressed together, like a shortcut
+    Keys(Vec<KeybdKey>),
+}
+
+// With special keys turned on, keys go in braces, like "{enter}" or "{ctrl+a}". Anything in braces that isn't a key is
+// typed as it is, and "{lbrace}" and "{rbrace}" type braces in case the text inside them is a key name.
+// It's off for strings saved before it existed, so braces in them are still typed.
+pub fn parse_typing_string(text: &str, special_keys: bool) -> Vec<TypingToken> {
+    let mut tokens: Vec<TypingToken> = Vec::new();
+    let mut rest: &str = text;
+    while let Some(character) = rest.chars().next() {
+        if character == '{' && special_keys {
+            if let Some(end) = rest.find('}') {
+                if let Some(token) = get_special_token(&rest[1..end]) {
+                    tokens.push(token);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        tokens.push(TypingToken::Character(character));
+        rest = &rest[character.len_utf8()..];
+    }
+    tokens
+}
+
+fn get_special_token(name: &str) -> Option<TypingToken> {
+    match name {
+        "lbrace" => Some(TypingToken::Character('{')),
+        "rbrace" => Some(TypingToken::Character('}')),
+        // Single characters in braces aren't special, so "{a}" is typed with the braces, and neither is anything with
+        // spaces, which is more likely to be code than a key
+        _ if name.chars().count() < 2 || name.chars().any(char::is_whitespace) => None,
+        _ => parse_hotkey(name).ok().map(TypingToken::Keys)
+    }
+}
+
+// Types the text one character at a time, waiting for the delay between each one
+pub fn type_string(text: &str, special_keys: bool, delay: Duration, run: &MacroRun) {
+    for (index, token) in parse_typing_string(text, special_keys).into_iter().enumerate() {
+        if index > 0 && !delay.is_zero() {
+            run.sleep(delay);
+        }
+        if run.should_stop() {
+            return;
+        }
+
+        match token {
+            TypingToken::Keys(keys) => tap_keys(&mut *run.input(), &keys),
+            TypingToken::Character(character) => {
+                if !run.input().type_character(character) {
+                    println!("Can't type \"{}\" on this platform", character);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::mem;
+    use winapi::um::winuser::{ SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE };
+
+    pub struct UnicodeTyper;
+
+    impl UnicodeTyper {
+        pub fn new() -> UnicodeTyper {
+            UnicodeTyper
+        }
+
+        pub fn type_character(&mut self, character: char) -> bool {
+            let mut units: [u16; 2] = [0; 2];
+            let mut inputs: Vec<INPUT> = Vec::new();
+            // Characters outside the basic plane are sent as two UTF-16 units
+            for unit in character.encode_utf16(&mut units).iter() {
+                for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+                    let mut input: INPUT = unsafe { mem::zeroed() };
+                    input.type_ = INPUT_KEYBOARD;
+                    unsafe {
+                        let keyboard = input.u.ki_mut();
+                        keyboard.wScan = *unit;
+                        keyboard.dwFlags = flags;
+                    }
+                    inputs.push(input);
+                }
+            }
+
+            let sent: u32 = unsafe { SendInput(inputs.len() as u32, inputs.as_mut_ptr(), mem::size_of::<INPUT>() as i32) };
+            sent as usize == inputs.len()
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use inputbot::KeybdKey;
+
+    use std::os::raw::{ c_int, c_void };
+    use std::ptr;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    use once_cell::sync::Lazy;
+    use x11_dl::xlib::{ Display, KeyCode, KeySym, Xlib };
+
+    // Every run types through the same spare keycode, so they take turns instead of changing its mapping under each other.
+    // Remapping keys only reaches X11 apps, so there's nothing to type with on Wayland.
+    static X11_TYPER: Lazy<Mutex<Option<X11Typer>>> = Lazy::new(|| Mutex::new(match std::env::var_os("WAYLAND_DISPLAY") {
+        Some(_) => None,
+        None => X11Typer::new()
+    }));
+
+    pub struct UnicodeTyper;
+
+    impl UnicodeTyper {
+        pub fn new() -> UnicodeTyper {
+            UnicodeTyper
+        }
+
+        pub fn type_character(&mut self, character: char) -> bool {
+            match X11_TYPER.lock().unwrap_or_else(|error| error.into_inner()).as_mut() {
+                Some(x11) => x11.type_character(character),
+                None => false
+            }
+        }
+    }
+
+    // This is part of sending input to the OS, so it doesn't go through a run's input backend
+    fn tap(keys: &[KeybdKey]) {
+        for key in keys {
+            key.press();
+        }
+        for key in keys.iter().rev() {
+            key.release();
+        }
+    }
+
+    // Like xdotool, characters that aren't on the keyboard are typed by mapping them to an unused keycode for a moment
+    struct X11Typer {
+        xlib: Xlib,
+        display: *mut Display,
+        keycode: c_int,
+    }
+
+    // The display is only used by whichever thread has X11_TYPER locked
+    unsafe impl Send for X11Typer {}
+
+    impl X11Typer {
+        fn new() -> Option<X11Typer> {
+            let xlib: Xlib = Xlib::open().ok()?;
+            unsafe {
+                let display: *mut Display = (xlib.XOpenDisplay)(ptr::null());
+                if display.is_null() {
+                    return None;
+                }
+                match find_unused_keycode(&xlib, display) {
+                    Some(keycode) => Some(X11Typer { xlib, display, keycode }),
+                    None => {
+                        (xlib.XCloseDisplay)(display);
+                        None
+                    }
+                }
+            }
+        }
+
+        fn type_character(&mut self, character: char) -> bool {
+            let keysym: KeySym = get_keysym(character);
+            self.set_keysym(keysym);
+            // Apps need a moment to see the new mapping before the key is pressed, and after it's released
+            thread::sleep(Duration::from_millis(20));
+            // X11 keycodes are 8 more than the evdev codes inputbot uses
+            tap(&[KeybdKey::OtherKey((self.keycode - 8) as u64)]);
+            thread::sleep(Duration::from_millis(20));
+            // Put the keycode back to unused, since the app could close before it would be put back otherwise
+            self.set_keysym(0);
+            true
+        }
+
+        fn set_keysym(&mut self, keysym: KeySym) {
+            let mut keysyms: [KeySym; 2] = [keysym, keysym];
+            unsafe {
+                (self.xlib.XChangeKeyboardMapping)(self.display, self.keycode, 2, keysyms.as_mut_ptr(), 1);
+                (self.xlib.XSync)(self.display, 0);
+            }
+        }
+    }
+
+    unsafe fn find_unused_keycode(xlib: &Xlib, display: *mut Display) -> Option<c_int> {
+        let mut min: c_int = 0;
+        let mut max: c_int = 0;
+        (xlib.XDisplayKeycodes)(display, &mut min, &mut max);
+
+        let mut keysyms_per_keycode: c_int = 0;
+        let keysyms: *mut KeySym = (xlib.XGetKeyboardMapping)(display, min as KeyCode, max - min + 1, &mut keysyms_per_keycode);
+        if keysyms.is_null() {
+            return None;
+        }
+
+        // Keycodes at the end are the least likely to belong to a real key
+        let unused: Option<c_int> = (min..=max).rev().find(|keycode| {
+            let start: usize = ((keycode - min) * keysyms_per_keycode) as usize;
+            (0..keysyms_per_keycode as usize).all(|index| *keysyms.add(start + index) == 0)
+        });
+        (xlib.XFree)(keysyms as *mut c_void);
+        unused
+    }
+
+    fn get_keysym(character: char) -> KeySym {
+        let code: u32 = character as u32;
+        // Latin-1 keysyms are the same as the character codes, and everything else is in the Unicode keysym range
+        if (0x20..=0x7e).contains(&code) || (0xa0..=0xff).contains(&code) {
+            code as KeySym
+        } else {
+            (0x0100_0000 | code) as KeySym
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod other {
+    pub struct UnicodeTyper;
+
+    impl UnicodeTyper {
+        pub fn new() -> UnicodeTyper {
+            UnicodeTyper
+        }
+
+        pub fn type_character(&mut self, _character: char) -> bool {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys are shown in braces, so the tokens can be compared as one string
+    fn describe(text: &str, special_keys: bool) -> String {
+        parse_typing_string(text, special_keys).into_iter().map(|token| match token {
+            TypingToken::Character(character) => character.to_string(),
+            TypingToken::Keys(keys) => format!("{:?}", keys),
+        }).collect()
+    }
+
+    #[test]
+    fn types_braces_unless_special_keys_are_on() {
+        assert_eq!(describe("if (a) {return}", false), "if (a) {return}");
+        assert_eq!(describe("{enter}{end}", false), "{enter}{end}");
+    }
+
+    #[test]
+    fn parses_special_keys() {
+        assert_eq!(describe("a{enter}b", true), "a[EnterKey]b");
+        assert_eq!(describe("{ctrl+a}", true), "[LControlKey, AKey]");
+        assert_eq!(describe("{lbrace}x{rbrace}", true), "{x}");
+    }
+
+    #[test]
+    fn types_anything_else_in_braces() {
+        assert_eq!(describe("{a}", true), "{a}");
+        assert_eq!(describe("{ b }", true), "{ b }");
+        assert_eq!(describe("{ctrl + a}", true), "{ctrl + a}");
+        assert_eq!(describe("{not a key}", true), "{not a key}");
+        assert_eq!(describe("{unclosed", true), "{unclosed");
+        assert_eq!(describe("{}", true), "{}");
+    }
+}
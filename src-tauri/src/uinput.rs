@@ -0,0 +1,438 @@
+use evdev::uinput::{ VirtualDevice, VirtualDeviceBuilder };
+use evdev::{ AttributeSet, Device, EventType, InputEvent, InputEventKind, Key, RelativeAxisType };
+
+use inputbot::{ KeybdKey, MouseButton };
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+use super::input::InputBackend;
+
+// Wayland doesn't let apps send input to other apps, so this sends it through a virtual device from /dev/uinput like a real
+// keyboard and mouse. Reading /dev/input and writing /dev/uinput usually means being in the "input" group.
+static VIRTUAL_DEVICE_NAME: &str = "Code Macros virtual input";
+
+// Made the first time it's needed and kept, since the desktop takes a moment to start using a new device
+static VIRTUAL_DEVICE: Lazy<Mutex<Option<VirtualDevice>>> = Lazy::new(|| Mutex::new(None));
+
+// Far enough to reach the top left corner of any screen layout
+static CORNER_DISTANCE: i32 = 100000;
+
+// The devices being read from, so checking for new ones doesn't read any twice
+static READING_DEVICES: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(|| Mutex::new(Vec::new()));
+// In seconds
+static DEVICE_SCAN_INTERVAL: u64 = 3;
+
+static EVDEV_KEYS: &[(KeybdKey, Key)] = &[
+    (KeybdKey::BackspaceKey,  Key::KEY_BACKSPACE),
+    (KeybdKey::TabKey,        Key::KEY_TAB),
+    (KeybdKey::EnterKey,      Key::KEY_ENTER),
+    (KeybdKey::EscapeKey,     Key::KEY_ESC),
+    (KeybdKey::SpaceKey,      Key::KEY_SPACE),
+    (KeybdKey::PageUpKey,     Key::KEY_PAGEUP),
+    (KeybdKey::PageDownKey,   Key::KEY_PAGEDOWN),
+    (KeybdKey::EndKey,        Key::KEY_END),
+    (KeybdKey::HomeKey,       Key::KEY_HOME),
+    (KeybdKey::LeftKey,       Key::KEY_LEFT),
+    (KeybdKey::UpKey,         Key::KEY_UP),
+    (KeybdKey::RightKey,      Key::KEY_RIGHT),
+    (KeybdKey::DownKey,       Key::KEY_DOWN),
+    (KeybdKey::InsertKey,     Key::KEY_INSERT),
+    (KeybdKey::DeleteKey,     Key::KEY_DELETE),
+    (KeybdKey::Numrow0Key,    Key::KEY_0),
+    (KeybdKey::Numrow1Key,    Key::KEY_1),
+    (KeybdKey::Numrow2Key,    Key::KEY_2),
+    (KeybdKey::Numrow3Key,    Key::KEY_3),
+    (KeybdKey::Numrow4Key,    Key::KEY_4),
+    (KeybdKey::Numrow5Key,    Key::KEY_5),
+    (KeybdKey::Numrow6Key,    Key::KEY_6),
+    (KeybdKey::Numrow7Key,    Key::KEY_7),
+    (KeybdKey::Numrow8Key,    Key::KEY_8),
+    (KeybdKey::Numrow9Key,    Key::KEY_9),
+    (KeybdKey::AKey,          Key::KEY_A),
+    (KeybdKey::BKey,          Key::KEY_B),
+    (KeybdKey::CKey,          Key::KEY_C),
+    (KeybdKey::DKey,          Key::KEY_D),
+    (KeybdKey::EKey,          Key::KEY_E),
+    (KeybdKey::FKey,          Key::KEY_F),
+    (KeybdKey::GKey,          Key::KEY_G),
+    (KeybdKey::HKey,          Key::KEY_H),
+    (KeybdKey::IKey,          Key::KEY_I),
+    (KeybdKey::JKey,          Key::KEY_J),
+    (KeybdKey::KKey,          Key::KEY_K),
+    (KeybdKey::LKey,          Key::KEY_L),
+    (KeybdKey::MKey,          Key::KEY_M),
+    (KeybdKey::NKey,          Key::KEY_N),
+    (KeybdKey::OKey,          Key::KEY_O),
+    (KeybdKey::PKey,          Key::KEY_P),
+    (KeybdKey::QKey,          Key::KEY_Q),
+    (KeybdKey::RKey,          Key::KEY_R),
+    (KeybdKey::SKey,          Key::KEY_S),
+    (KeybdKey::TKey,          Key::KEY_T),
+    (KeybdKey::UKey,          Key::KEY_U),
+    (KeybdKey::VKey,          Key::KEY_V),
+    (KeybdKey::WKey,          Key::KEY_W),
+    (KeybdKey::XKey,          Key::KEY_X),
+    (KeybdKey::YKey,          Key::KEY_Y),
+    (KeybdKey::ZKey,          Key::KEY_Z),
+    (KeybdKey::LSuper,        Key::KEY_LEFTMETA),
+    (KeybdKey::RSuper,        Key::KEY_RIGHTMETA),
+    (KeybdKey::Numpad0Key,    Key::KEY_KP0),
+    (KeybdKey::Numpad1Key,    Key::KEY_KP1),
+    (KeybdKey::Numpad2Key,    Key::KEY_KP2),
+    (KeybdKey::Numpad3Key,    Key::KEY_KP3),
+    (KeybdKey::Numpad4Key,    Key::KEY_KP4),
+    (KeybdKey::Numpad5Key,    Key::KEY_KP5),
+    (KeybdKey::Numpad6Key,    Key::KEY_KP6),
+    (KeybdKey::Numpad7Key,    Key::KEY_KP7),
+    (KeybdKey::Numpad8Key,    Key::KEY_KP8),
+    (KeybdKey::Numpad9Key,    Key::KEY_KP9),
+    (KeybdKey::F1Key,         Key::KEY_F1),
+    (KeybdKey::F2Key,         Key::KEY_F2),
+    (KeybdKey::F3Key,         Key::KEY_F3),
+    (KeybdKey::F4Key,         Key::KEY_F4),
+    (KeybdKey::F5Key,         Key::KEY_F5),
+    (KeybdKey::F6Key,         Key::KEY_F6),
+    (KeybdKey::F7Key,         Key::KEY_F7),
+    (KeybdKey::F8Key,         Key::KEY_F8),
+    (KeybdKey::F9Key,         Key::KEY_F9),
+    (KeybdKey::F10Key,        Key::KEY_F10),
+    (KeybdKey::F11Key,        Key::KEY_F11),
+    (KeybdKey::F12Key,        Key::KEY_F12),
+    (KeybdKey::NumLockKey,    Key::KEY_NUMLOCK),
+    (KeybdKey::ScrollLockKey, Key::KEY_SCROLLLOCK),
+    (KeybdKey::CapsLockKey,   Key::KEY_CAPSLOCK),
+    (KeybdKey::LShiftKey,     Key::KEY_LEFTSHIFT),
+    (KeybdKey::RShiftKey,     Key::KEY_RIGHTSHIFT),
+    (KeybdKey::LControlKey,   Key::KEY_LEFTCTRL),
+    (KeybdKey::RControlKey,   Key::KEY_RIGHTCTRL),
+    (KeybdKey::LAltKey,       Key::KEY_LEFTALT),
+    (KeybdKey::RAltKey,       Key::KEY_RIGHTALT),
+    (KeybdKey::BackquoteKey,  Key::KEY_GRAVE),
+    (KeybdKey::SlashKey,      Key::KEY_SLASH),
+    (KeybdKey::BackslashKey,  Key::KEY_BACKSLASH),
+    (KeybdKey::CommaKey,      Key::KEY_COMMA),
+    (KeybdKey::PeriodKey,     Key::KEY_DOT),
+    (KeybdKey::MinusKey,      Key::KEY_MINUS),
+    (KeybdKey::QuoteKey,      Key::KEY_APOSTROPHE),
+    (KeybdKey::SemicolonKey,  Key::KEY_SEMICOLON),
+    (KeybdKey::LBracketKey,   Key::KEY_LEFTBRACE),
+    (KeybdKey::RBracketKey,   Key::KEY_RIGHTBRACE),
+    (KeybdKey::EqualKey,      Key::KEY_EQUAL),
+];
+
+fn get_evdev_key(key: KeybdKey) -> Key {
+    match EVDEV_KEYS.iter().find(|(keybd_key, _)| *keybd_key == key) {
+        Some((_, evdev_key)) => *evdev_key,
+        // Other keys already use evdev codes
+        None => match key {
+            KeybdKey::OtherKey(code) => Key::new(code as u16),
+            _ => Key::KEY_UNKNOWN
+        }
+    }
+}
+
+fn get_keybd_key(key: Key) -> KeybdKey {
+    match EVDEV_KEYS.iter().find(|(_, evdev_key)| *evdev_key == key) {
+        Some((keybd_key, _)) => *keybd_key,
+        None => KeybdKey::OtherKey(key.code() as u64)
+    }
+}
+
+static EVDEV_BUTTONS: [(MouseButton, Key); 5] = [
+    (MouseButton::LeftButton,   Key::BTN_LEFT),
+    (MouseButton::RightButton,  Key::BTN_RIGHT),
+    (MouseButton::MiddleButton, Key::BTN_MIDDLE),
+    (MouseButton::X1Button,     Key::BTN_SIDE),
+    (MouseButton::X2Button,     Key::BTN_EXTRA),
+];
+
+fn get_evdev_button(button: MouseButton) -> Key {
+    match EVDEV_BUTTONS.iter().find(|(mouse_button, _)| *mouse_button == button) {
+        Some((_, evdev_key)) => *evdev_key,
+        None => Key::KEY_UNKNOWN
+    }
+}
+
+// Codes from BTN_0 up to KEY_OK are buttons rather than keys
+fn is_button(key: Key) -> bool {
+    key.code() >= Key::BTN_0.code() && key.code() < Key::KEY_OK.code()
+}
+
+fn get_mouse_button(key: Key) -> Option<MouseButton> {
+    EVDEV_BUTTONS.iter().find(|(_, evdev_key)| *evdev_key == key).map(|(mouse_button, _)| *mouse_button)
+}
+
+fn create_virtual_device() -> std::io::Result<VirtualDevice> {
+    // Every key and button, so anything can be sent without making another device
+    let mut keys: AttributeSet<Key> = AttributeSet::new();
+    for code in 1..Key::BTN_TRIGGER_HAPPY1.code() {
+        keys.insert(Key::new(code));
+    }
+
+    let mut axes: AttributeSet<RelativeAxisType> = AttributeSet::new();
+    for axis in [RelativeAxisType::REL_X, RelativeAxisType::REL_Y, RelativeAxisType::REL_WHEEL, RelativeAxisType::REL_HWHEEL] {
+        axes.insert(axis);
+    }
+
+    let device: VirtualDevice = VirtualDeviceBuilder::new()?
+        .name(VIRTUAL_DEVICE_NAME)
+        .with_keys(&keys)?
+        .with_relative_axes(&axes)?
+        .build()?;
+    // The desktop ignores input from a new device until it has noticed it
+    thread::sleep(Duration::from_millis(200));
+    Ok(device)
+}
+
+fn emit(events: &[InputEvent]) {
+    let mut device = VIRTUAL_DEVICE.lock().unwrap();
+    if let Some(device) = device.as_mut() {
+        if let Err(error) = device.emit(events) {
+            println!("Failed to send input through uinput: {}", error);
+        }
+    }
+}
+
+pub struct UinputBackend {
+    // Wayland doesn't let apps see where the mouse is, so it's kept track of after the mouse is moved to a position
+    position: Option<(i32, i32)>,
+}
+
+impl UinputBackend {
+    pub fn new() -> Result<UinputBackend, String> {
+        let mut device = VIRTUAL_DEVICE.lock().unwrap();
+        if device.is_none() {
+            *device = Some(create_virtual_device().map_err(|error| format!("Can't create a uinput device: {}", error))?);
+        }
+        Ok(UinputBackend { position: None })
+    }
+
+    // Only if the virtual device has already been made, since a new one can't release what the old one pressed
+    pub fn existing() -> Option<UinputBackend> {
+        if VIRTUAL_DEVICE.lock().unwrap().is_none() {
+            return None;
+        }
+        Some(UinputBackend { position: None })
+    }
+
+    fn send_key(&mut self, key: Key, value: i32) {
+        emit(&[InputEvent::new(EventType::KEY, key.code(), value)]);
+    }
+}
+
+impl InputBackend for UinputBackend {
+    fn press_key(&mut self, key: KeybdKey) {
+        self.send_key(get_evdev_key(key), 1);
+    }
+
+    fn release_key(&mut self, key: KeybdKey) {
+        self.send_key(get_evdev_key(key), 0);
+    }
+
+    // A virtual keyboard can only press keys. Shortcuts like ctrl+shift+u only work in some apps, and type junk in the rest.
+    fn type_unicode(&mut self, _character: char) -> bool {
+        false
+    }
+
+    fn press_button(&mut self, button: MouseButton) {
+        self.send_key(get_evdev_button(button), 1);
+    }
+
+    fn release_button(&mut self, button: MouseButton) {
+        self.send_key(get_evdev_button(button), 0);
+    }
+
+    fn move_mouse_relative(&mut self, x: i32, y: i32) {
+        emit(&[
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, x),
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_Y.0, y),
+        ]);
+        self.position = self.position.map(|position| (position.0 + x, position.1 + y));
+    }
+
+    // Like ydotool, this goes to the top left corner and then moves from there, since a mouse can only move relatively.
+    // It's only exact if mouse acceleration is turned off.
+    fn move_mouse_absolute(&mut self, x: i32, y: i32) {
+        self.move_mouse_relative(-CORNER_DISTANCE, -CORNER_DISTANCE);
+        self.move_mouse_relative(x, y);
+        self.position = Some((x, y));
+    }
+
+    fn scroll_vertical(&mut self, amount: i32) {
+        emit(&[InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_WHEEL.0, amount)]);
+    }
+
+    fn scroll_horizontal(&mut self, amount: i32) {
+        emit(&[InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_HWHEEL.0, amount)]);
+    }
+
+    fn get_mouse_position(&self) -> Result<(i32, i32), String> {
+        self.position.ok_or_else(|| "Where the mouse is isn't known with uinput until a macro moves it to a position".to_string())
+    }
+}
+
+pub enum EvdevInput {
+    Key(KeybdKey, bool),
+    // Only presses, like inputbot's mouse binds
+    Button(MouseButton),
+    // Notches of the horizontal and vertical scroll wheels. Positive is right or up.
+    Scroll(i32, i32),
+}
+
+// Reads keys, mouse buttons and the scroll wheel from every keyboard and mouse, for sessions where inputbot can't see them.
+// Devices plugged in later are picked up by checking for new ones every few seconds.
+pub fn listen_evdev_input<F: Fn(EvdevInput) + Clone + Send + 'static>(callback: F) {
+    thread::spawn(move || {
+        let mut first_scan: bool = true;
+        loop {
+            let devices: Vec<(PathBuf, Device)> = {
+                let reading = READING_DEVICES.lock().unwrap();
+                evdev::enumerate()
+                    .filter(|(path, _)| !reading.contains(path))
+                    // Input the macros send shouldn't trigger other macros
+                    .filter(|(_, device)| device.name() != Some(VIRTUAL_DEVICE_NAME))
+                    .filter(|(_, device)| is_keyboard_or_mouse(device))
+                    .collect()
+            };
+            if first_scan && devices.is_empty() {
+                println!("No keyboards or mice could be read from /dev/input. Is this user in the input group?");
+            }
+            first_scan = false;
+
+            for (path, device) in devices {
+                READING_DEVICES.lock().unwrap().push(path.clone());
+                read_device(path, device, callback.clone());
+            }
+            thread::sleep(Duration::from_secs(DEVICE_SCAN_INTERVAL));
+        }
+    });
+}
+
+fn is_keyboard_or_mouse(device: &Device) -> bool {
+    device.supported_keys().map_or(false, |keys| keys.contains(Key::KEY_A) || keys.contains(Key::BTN_LEFT))
+        || device.supported_relative_axes().map_or(false, |axes| axes.contains(RelativeAxisType::REL_WHEEL))
+}
+
+fn read_device<F: Fn(EvdevInput) + Send + 'static>(path: PathBuf, mut device: Device, callback: F) {
+    let name: String = device.name().unwrap_or("a device").to_string();
+    thread::spawn(move || {
+        // What this device is holding, since its releases never come if it's unplugged
+        let mut held: Vec<KeybdKey> = Vec::new();
+        loop {
+            let events = match device.fetch_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    println!("Stopped reading input from {}: {}", name, error);
+                    for key in held {
+                        callback(EvdevInput::Key(key, false));
+                    }
+                    READING_DEVICES.lock().unwrap().retain(|reading| *reading != path);
+                    return;
+                }
+            };
+            for event in events {
+                match event.kind() {
+                    // Touchpads and joysticks send buttons too, which aren't keys anyone can bind
+                    InputEventKind::Key(key) if is_button(key) => {
+                        if let (Some(button), 1) = (get_mouse_button(key), event.value()) {
+                            callback(EvdevInput::Button(button));
+                        }
+                    }
+                    // A value of 2 is the key repeating while it's held, which isn't a new press
+                    InputEventKind::Key(key) => {
+                        let keybd_key: KeybdKey = get_keybd_key(key);
+                        match event.value() {
+                            1 => {
+                                held.push(keybd_key);
+                                callback(EvdevInput::Key(keybd_key, true));
+                            }
+                            0 => {
+                                held.retain(|held_key| *held_key != keybd_key);
+                                callback(EvdevInput::Key(keybd_key, false));
+                            }
+                            _ => {}
+                        }
+                    }
+                    InputEventKind::RelAxis(RelativeAxisType::REL_WHEEL) => callback(EvdevInput::Scroll(0, event.value())),
+                    InputEventKind::RelAxis(RelativeAxisType::REL_HWHEEL) => callback(EvdevInput::Scroll(event.value(), 0)),
+                    _ => {}
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn converts_keys_both_ways() {
+        for (keybd_key, evdev_key) in EVDEV_KEYS {
+            assert_eq!(get_evdev_key(*keybd_key), *evdev_key);
+            assert_eq!(get_keybd_key(*evdev_key), *keybd_key);
+        }
+        assert_eq!(get_keybd_key(Key::KEY_F13), KeybdKey::OtherKey(Key::KEY_F13.code() as u64));
+        assert_eq!(get_evdev_key(KeybdKey::OtherKey(Key::KEY_F13.code() as u64)), Key::KEY_F13);
+
+        for (mouse_button, evdev_key) in EVDEV_BUTTONS {
+            assert_eq!(get_evdev_button(mouse_button), evdev_key);
+            assert_eq!(get_mouse_button(evdev_key), Some(mouse_button));
+            assert!(is_button(evdev_key));
+        }
+        assert!(!is_button(Key::KEY_A));
+    }
+
+    // Needs write access to /dev/uinput and read access to /dev/input, so it's skipped anywhere without them
+    #[test]
+    fn sends_input_through_the_virtual_device() {
+        if OpenOptions::new().write(true).open("/dev/uinput").is_err() {
+            println!("Skipping, /dev/uinput can't be written to");
+            return;
+        }
+        let mut backend: UinputBackend = UinputBackend::new().unwrap();
+        let mut device: Device = match evdev::enumerate().map(|(_, device)| device).find(|device| device.name() == Some(VIRTUAL_DEVICE_NAME)) {
+            Some(device) => device,
+            None => {
+                println!("Skipping, the virtual device can't be read from /dev/input");
+                return;
+            }
+        };
+
+        backend.press_key(KeybdKey::AKey);
+        backend.release_key(KeybdKey::AKey);
+        backend.press_button(MouseButton::RightButton);
+        backend.release_button(MouseButton::RightButton);
+        backend.move_mouse_relative(5, -3);
+        backend.scroll_vertical(-1);
+        assert!(backend.get_mouse_position().is_err());
+        backend.move_mouse_absolute(10, 20);
+        assert_eq!(backend.get_mouse_position(), Ok((10, 20)));
+
+        let expected: Vec<(InputEventKind, i32)> = vec![
+            (InputEventKind::Key(Key::KEY_A), 1),
+            (InputEventKind::Key(Key::KEY_A), 0),
+            (InputEventKind::Key(Key::BTN_RIGHT), 1),
+            (InputEventKind::Key(Key::BTN_RIGHT), 0),
+            (InputEventKind::RelAxis(RelativeAxisType::REL_X), 5),
+            (InputEventKind::RelAxis(RelativeAxisType::REL_Y), -3),
+            (InputEventKind::RelAxis(RelativeAxisType::REL_WHEEL), -1),
+        ];
+        let mut received: Vec<(InputEventKind, i32)> = Vec::new();
+        while received.len() < expected.len() {
+            for event in device.fetch_events().unwrap() {
+                if event.event_type() != EventType::SYNCHRONIZATION {
+                    received.push((event.kind(), event.value()));
+                }
+            }
+        }
+        assert_eq!(received[..expected.len()], expected[..]);
+    }
+}
@@ -1,4 +1,4 @@
-export default [
+const codeTypes = [
   {
     name: "If", value: "if", description: "Executes the code inside if the condition is true.", parameters: [
       {
@@ -83,7 +83,12 @@ export default [
   },
   {
     name: "Type string", value: "typestring", description: "Type a string", parameters: [
-      { name: "String", value: "string", description: "The string you want to type.", type: "string", defaultValue: "", },
+      { name: "String", value: "string", description: "The string you want to type", type: "string", defaultValue: "", },
+      { name: "Delay", value: "delay", description: "How long to wait between each character in milliseconds", type: "number", defaultValue: 0, },
+      { name: "Braces", value: "braces", description: "Whether keys can be pressed by naming them in braces", type: "multiSelect", defaultValue: "text", options: [
+        { name: "Type braces", value: "text", description: "Type braces like any other character"},
+        { name: "Special keys", value: "keys", description: "Press the keys named in braces, like {enter} or {ctrl+a}. {lbrace} and {rbrace} type braces."},
+      ] },
     ], contentText: (parameters) => {
       return [`Type the string`, {type: "string", string: parameters.string}];
     },
@@ -93,16 +98,34 @@ export default [
     },
   },
   { name: "Move mouse relative", value: "movemouserelative", description: "Move the mouse relative to it's current position.", parameters: [
-    { name: "X", value: "x", description: "How much to move the mouse horizontally", type: "number", defaultValue: 0, },
-    { name: "Y", value: "y", description: "How much to move the mouse vertically", type: "number", defaultValue: 0, },
-  ], contentText: (parameters) => {
-    return `Move the mouse by (${parameters["x"]}, ${parameters["y"]})`;
+    { name: "X", value: "x", description: "How much to move the mouse horizontally", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "Y", value: "y", description: "How much to move the mouse vertically", type: "expression", defaultValue: { type: "number", value: 0 }, },
+  ], contentText: (parameters, parsers) => {
+    return [`Move the mouse by`, ...parsers.parseExpression(parameters.x), `horizontally and`, ...parsers.parseExpression(parameters.y), `vertically`];
   }},
   { name: "Move mouse absolute", value: "movemouseabsolute", description: "Move the mouse to a position on the screen.", parameters: [
-    { name: "X", value: "x", description: "The horizontal position of the mouse", type: "number", defaultValue: 0, },
-    { name: "Y", value: "y", description: "The vertical position of the mouse", type: "number", defaultValue: 0, },
+    { name: "X", value: "x", description: "The horizontal position of the mouse", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "Y", value: "y", description: "The vertical position of the mouse", type: "expression", defaultValue: { type: "number", value: 0 }, },
+  ], contentText: (parameters, parsers) => {
+    return [`Move the mouse to x`, ...parsers.parseExpression(parameters.x), `and y`, ...parsers.parseExpression(parameters.y)];
+  }},
+  { name: "Get mouse position", value: "getmouseposition", description: "Get where the mouse is on the screen.", parameters: [
+    { name: "X", value: "x", description: "The variable to set to the horizontal position", type: "string", defaultValue: "mouseX" },
+    { name: "Y", value: "y", description: "The variable to set to the vertical position", type: "string", defaultValue: "mouseY" },
+  ], contentText: (parameters) => {
+    return [`Set the variables`, {type: "variable", variable: parameters.x}, `and`, {type: "variable", variable: parameters.y}, `to the mouse position`];
+  }},
+  { name: "Get screen size", value: "getscreensize", description: "Get the size and position of a monitor. Leave a variable empty to skip it.", parameters: [
+    { name: "Monitor", value: "monitor", description: "Which monitor to get", type: "multiSelect", defaultValue: "primary", options: [
+      { name: "Main monitor", value: "primary", description: "The main monitor"},
+      { name: "Monitor with the mouse", value: "mouse", description: "The monitor the mouse is on"},
+    ] },
+    { name: "Width", value: "width", description: "The variable to set to the width", type: "string", defaultValue: "screenWidth" },
+    { name: "Height", value: "height", description: "The variable to set to the height", type: "string", defaultValue: "screenHeight" },
+    { name: "X", value: "x", description: "The variable to set to the horizontal position of the monitor's top left corner", type: "string", defaultValue: "" },
+    { name: "Y", value: "y", description: "The variable to set to the vertical position of the monitor's top left corner", type: "string", defaultValue: "" },
   ], contentText: (parameters) => {
-    return `Move the mouse to (${parameters["x"]}, ${parameters["y"]})`;
+    return [`Set the variables`, {type: "variable", variable: parameters.width}, `and`, {type: "variable", variable: parameters.height}, `to the size of the screen`];
   }},
   { name: "Press key", value: "presskey", description: "Start pressing a key.", parameters: [
     { name: "Key", value: "key", description: "The key to press", type: "string", defaultValue: 0, }
@@ -114,10 +137,18 @@ export default [
   ], contentText: (parameters) => {
     return [`Release the key`, {type: "string", string: parameters.key}];
   }},
+  { name: "Send hotkey", value: "sendhotkey", description: "Press a key combination, like a keyboard shortcut.", parameters: [
+    { name: "Keys", value: "keys", description: "The keys to press, joined with +. Modifiers go first, like ctrl+shift+t or super+enter.", type: "string", defaultValue: "", }
+  ], contentText: (parameters) => {
+    return [`Press the hotkey`, {type: "string", string: parameters.keys}];
+  }},
   { name: "Press mouse", value: "pressmouse", description: "Start pressing a mouse button.", parameters: [
     { name: "Button", value: "button", description: "The mouse button to press", type: "multiSelect", defaultValue: "LMB", options: [
       { name: "Left mouse button", value: "LMB", description: "The left mouse button"},
       { name: "Right mouse button", value: "RMB", description: "The right mouse button"},
+      { name: "Middle mouse button", value: "MMB", description: "The middle mouse button, usually the scroll wheel"},
+      { name: "Back button", value: "X1", description: "The first side button"},
+      { name: "Forward button", value: "X2", description: "The second side button"},
     ] }
   ], contentText: (parameters) => {
     return [`Start pressing the mouse button`, {type: "string", string: parameters.button}];
@@ -126,10 +157,51 @@ export default [
     { name: "Button", value: "button", description: "The mouse button to press", type: "multiSelect", defaultValue: "LMB", options: [
       { name: "Left mouse button", value: "LMB", description: "The left mouse button"},
       { name: "Right mouse button", value: "RMB", description: "The right mouse button"},
+      { name: "Middle mouse button", value: "MMB", description: "The middle mouse button, usually the scroll wheel"},
+      { name: "Back button", value: "X1", description: "The first side button"},
+      { name: "Forward button", value: "X2", description: "The second side button"},
     ] }
   ], contentText: (parameters) => {
     return [`Release the mouse button`, {type: "string", string: parameters.button}];
   }},
+  { name: "Click", value: "click", description: "Click a mouse button one or more times.", parameters: [
+    { name: "Button", value: "button", description: "The mouse button to click", type: "multiSelect", defaultValue: "LMB", options: [
+      { name: "Left mouse button", value: "LMB", description: "The left mouse button"},
+      { name: "Right mouse button", value: "RMB", description: "The right mouse button"},
+      { name: "Middle mouse button", value: "MMB", description: "The middle mouse button, usually the scroll wheel"},
+      { name: "Back button", value: "X1", description: "The first side button"},
+      { name: "Forward button", value: "X2", description: "The second side button"},
+    ] },
+    { name: "Count", value: "count", description: "How many times to click, like 2 for a double click", type: "number", defaultValue: 1, }
+  ], contentText: (parameters) => {
+    return [`Click the mouse button`, {type: "string", string: parameters.button}, `${parameters.count} times`];
+  }},
+  { name: "Scroll vertically", value: "scrollvertical", description: "Scroll up or down.", parameters: [
+    { name: "Amount", value: "amount", description: "How many notches to scroll. Positive scrolls up and negative scrolls down.", type: "number", defaultValue: 1, }
+  ], contentText: (parameters) => {
+    return `Scroll vertically by ${parameters.amount}`;
+  }},
+  { name: "Scroll horizontally", value: "scrollhorizontal", description: "Scroll left or right.", parameters: [
+    { name: "Amount", value: "amount", description: "How many notches to scroll. Positive scrolls right and negative scrolls left.", type: "number", defaultValue: 1, }
+  ], contentText: (parameters) => {
+    return `Scroll horizontally by ${parameters.amount}`;
+  }},
+  { name: "Drag", value: "drag", description: "Hold a mouse button while moving from one point to another.", parameters: [
+    { name: "Button", value: "button", description: "The mouse button to hold", type: "multiSelect", defaultValue: "LMB", options: [
+      { name: "Left mouse button", value: "LMB", description: "The left mouse button"},
+      { name: "Right mouse button", value: "RMB", description: "The right mouse button"},
+      { name: "Middle mouse button", value: "MMB", description: "The middle mouse button, usually the scroll wheel"},
+      { name: "Back button", value: "X1", description: "The first side button"},
+      { name: "Forward button", value: "X2", description: "The second side button"},
+    ] },
+    { name: "From X", value: "fromx", description: "Where to start dragging horizontally", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "From Y", value: "fromy", description: "Where to start dragging vertically", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "To X", value: "tox", description: "Where to stop dragging horizontally", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "To Y", value: "toy", description: "Where to stop dragging vertically", type: "expression", defaultValue: { type: "number", value: 0 }, },
+    { name: "Duration", value: "duration", description: "How long the drag takes in seconds", type: "number", defaultValue: 0.5, },
+  ], contentText: (parameters, parsers) => {
+    return [`Drag ${parameters.button} from`, ...parsers.parseExpression(parameters.fromx), `,`, ...parsers.parseExpression(parameters.fromy), `to`, ...parsers.parseExpression(parameters.tox), `,`, ...parsers.parseExpression(parameters.toy)];
+  }},
   { name: "Read file", value: "readfile", description: "Read a file at a certain path.", parameters: [
     { name: "File", value: "file", description: "The file to read", type: "string", defaultValue: "C:/" },
     { name: "Variable", value: "variable", description: "The variable you want to set", type: "string", defaultValue: "fileContent" }
@@ -223,12 +295,20 @@ export default [
   ], contentText: (parameters) => {
     return [`Get the contents of the folder`, { type: "variable", variable: parameters.folder }, `and set the variable`, { type: "variable", variable: parameters.output }];
   }},
-  { name: "Log", value: "log", description: "Add a message to the log.", parameters: [
-    { name: "Message", value: "message", description: "The message to log", type: "string", defaultValue: "Hello world!" },
+  { name: "Log", value: "log", description: "Add a message to the macro's log.", parameters: [
+    { name: "Message", value: "message", description: "The message to log. Variables can be added like {{myVariable}}", type: "string", defaultValue: "Hello world!" },
+    { name: "Level", value: "level", description: "How important the message is", type: "multiSelect", defaultValue: "info", options: [
+      { name: "Info", value: "info", description: "A normal message"},
+      { name: "Warning", value: "warning", description: "Something that might be a problem"},
+      { name: "Error", value: "error", description: "Something that went wrong"},
+    ] }
   ], contentText: (parameters) => {
+    if(parameters.level && parameters.level !== "info") {
+      return [`Log the ${parameters.level}`, { type: "string", string: parameters.message }];
+    }
     return [`Log the message`, { type: "string", string: parameters.message }];
   }},
-  { name: "Clear log", value: "clearlog", description: "Clear the log.", parameters: [], contentText: (parameters) => {
+  { name: "Clear log", value: "clearlog", description: "Clear the macro's log.", parameters: [], contentText: (parameters) => {
     return ``;
   }},
   // TODO: Make it so code groups can be layered in each other so string manipulation can be a part of variables
@@ -258,4 +338,53 @@ export default [
   ], contentText: (parameters) => {
     return [`Sort the array`, { type: "variable", variable: parameters.array }, `and set the variable`, { type: "variable", variable: parameters.output }];
   }},
-];
\ No newline at end of file
+  { name: "Get clipboard", value: "getclipboard", description: "Get the text on the clipboard.", parameters: [
+    { name: "Output", value: "output", description: "The variable to set to the clipboard text", type: "string", defaultValue: "clipboardText" }
+  ], contentText: (parameters) => {
+    return [`Get the text on the clipboard and set the variable`, { type: "variable", variable: parameters.output }];
+  }},
+  { name: "Set clipboard", value: "setclipboard", description: "Put text on the clipboard.", parameters: [
+    { name: "Content", value: "content", description: "The text to put on the clipboard", type: "string", defaultValue: "Hello, world!" }
+  ], contentText: (parameters) => {
+    return [`Put`, { type: "string", string: parameters.content }, `on the clipboard`];
+  }},
+  { name: "Run macro", value: "runmacro", description: "Run another macro's initiator. If it sets a variable named result, that can be used after it finishes.", parameters: [
+    { name: "Macro", value: "macro", description: "The name of the macro to run", type: "string", defaultValue: "My macro" },
+    { name: "Initiator", value: "initiator", description: "Which of the macro's initiators to run, starting at 0", type: "number", defaultValue: 0 },
+    { name: "Inputs", value: "inputs", description: "Variables to give to the macro, separated by commas", type: "string", defaultValue: "" },
+    { name: "Wait", value: "wait", description: "Whether to wait for the macro to finish", type: "multiSelect", defaultValue: "wait", options: [
+      { name: "Wait for it to finish", value: "wait", description: "Wait for the macro to finish and set the output to its result"},
+      { name: "Run in the background", value: "background", description: "Keep going while the macro runs"},
+    ] },
+    { name: "Output", value: "output", description: "The variable to set to the macro's result", type: "string", defaultValue: "macroResult" }
+  ], contentText: (parameters) => {
+    if(parameters.wait === "wait") {
+      return [`Run the macro`, { type: "string", string: parameters.macro }, `and set the variable`, { type: "variable", variable: parameters.output }, `to its result`];
+    }
+    return [`Run the macro`, { type: "string", string: parameters.macro }, `in the background`];
+  }},
+];
+
+export default codeTypes;
+
+// Mouse coordinates used to be plain numbers before they could be expressions, so wrap old ones when a macro is loaded
+function normalizeExecutes(executes) {
+  for(const execute of executes || []) {
+    const codeType = codeTypes.find(codeType => codeType.value === execute?.type);
+    for(const parameter of codeType?.parameters || []) {
+      if(parameter.type === "expression" && typeof execute.data?.[parameter.value] === "number") {
+        execute.data[parameter.value] = { type: "number", value: execute.data[parameter.value] };
+      }
+    }
+    for(const codeInside of Object.values(execute?.codeInside || {})) {
+      normalizeExecutes(codeInside?.executes);
+    }
+  }
+}
+
+export function normalizeMacro(macro) {
+  for(const code of [...(macro?.macro?.initiators || []), ...(macro?.macro?.functions || [])]) {
+    normalizeExecutes(code?.executes);
+  }
+  return macro;
+}
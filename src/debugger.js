@@ -0,0 +1,64 @@
+import { reactive } from 'vue';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+
+// Shared by the code blocks, which show the breakpoints, and the panel with the debugger controls
+export const debugState = reactive({
+    breakpoints: [],
+    id: null,
+    starting: false,
+    paused: null,
+    finished: null
+});
+
+// Node paths look like "initiator:0/2/then/1" or "function:My macro:0/1", the same as the backend uses for breakpoints
+export function getNodePath(position, index, macroName) {
+    const [first, ...rest] = position.treePosition;
+    const owner = position.type === "Function" ? `${macroName}:` : "";
+    return [`${position.type.toLowerCase()}:${owner}${first}`, ...rest, index].join('/');
+}
+
+export function toggleBreakpoint(path) {
+    if(debugState.breakpoints.includes(path)) {
+        debugState.breakpoints.splice(debugState.breakpoints.indexOf(path), 1);
+    } else {
+        debugState.breakpoints.push(path);
+    }
+    if(debugState.id !== null) {
+        invoke('set_breakpoints', { id: debugState.id, breakpoints: [...debugState.breakpoints] });
+    }
+}
+
+export async function startDebugging(macroName, initiator) {
+    debugState.paused = null;
+    debugState.finished = null;
+    debugState.starting = true;
+    try {
+        debugState.id = await invoke('debug_macro', { macroName, initiator, variables: null, breakpoints: [...debugState.breakpoints] });
+    } finally {
+        debugState.starting = false;
+    }
+}
+
+export function sendDebugCommand(command) {
+    if(debugState.id === null) return;
+    debugState.paused = null;
+    invoke('debug_command', { id: debugState.id, command });
+}
+
+export function listenForDebugEvents() {
+    listen('debug-paused', (event) => {
+        // The run can pause before the command that started it returns its ID
+        if(debugState.id === null && debugState.starting) {
+            debugState.id = event.payload.id;
+        }
+        if(event.payload.id !== debugState.id) return;
+        debugState.paused = event.payload;
+    });
+    listen('debug-finished', (event) => {
+        if(event.payload.id !== debugState.id) return;
+        debugState.id = null;
+        debugState.paused = null;
+        debugState.finished = event.payload;
+    });
+}
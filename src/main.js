@@ -3,10 +3,15 @@ import App from './App.vue';
 import router from './router';
 import updateMacros from './utils';
 
-import { loadTheme } from "@/utils";
+import { loadTheme, updateSettings, updatePaused, listenForStateChanges } from "@/utils";
+import { listenForDebugEvents } from "@/debugger";
 
 loadTheme();
 
 createApp(App).use(router).mount('#app');
 
-updateMacros();
\ No newline at end of file
+listenForStateChanges();
+listenForDebugEvents();
+updateMacros();
+updateSettings();
+updatePaused();
\ No newline at end of file
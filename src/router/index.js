@@ -5,6 +5,7 @@ import MacrosList from "@/views/MacrosList.vue";
 import WelcomePage from "@/views/WelcomePage.vue";
 import SettingsPage from "@/views/SettingsPage.vue";
 import LogPage from "@/views/LogPage.vue";
+import HistoryPage from "@/views/HistoryPage.vue";
 
 const routes = [
   {
@@ -36,6 +37,11 @@ const routes = [
     path: "/log",
     name: "Log",
     component: LogPage,
+  },
+  {
+    path: "/history",
+    name: "History",
+    component: HistoryPage,
   }
 ];
 
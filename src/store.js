@@ -1,4 +1,14 @@
-import { BaseDirectory, createDir, writeFile, readTextFile } from "@tauri-apps/api/fs";
+import { createDir, writeFile, readTextFile } from "@tauri-apps/api/fs";
+import { invoke } from "@tauri-apps/api/tauri";
+
+// The backend decides where the data folder is, since it can be moved with --data-dir or CODEMACROS_DATA_DIR
+let dataPaths = null;
+function getDataPaths() {
+    if(dataPaths === null) {
+        dataPaths = invoke("get_paths");
+    }
+    return dataPaths;
+}
 
 export async function get(key, defaultValue) {
     return new Promise(async (resolve, reject) => {
@@ -28,26 +38,23 @@ export async function set(key, value) {
 }
 
 const createDataFolder = async () => {
-    await createDir("CodeMacros", {
-        dir: BaseDirectory.Config,
+    const { dataDir } = await getDataPaths();
+    await createDir(dataDir, {
         recursive: true,
     })
 };
 
 const setFileData = async (data) => {
+    const { config } = await getDataPaths();
     return writeFile(
         {
             contents: JSON.stringify(data),
-            path: `CodeMacros/config.json`,
-        },
-        {
-            dir: BaseDirectory.Config,
+            path: config,
         }
     );
 };
 
-const readDataFile = () => {
-    return readTextFile("CodeMacros/config.json", {
-        dir: BaseDirectory.Config,
-    })
+const readDataFile = async () => {
+    const { config } = await getDataPaths();
+    return readTextFile(config);
 }
\ No newline at end of file
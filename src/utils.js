@@ -1,12 +1,14 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import * as store from './store';
+import { normalizeMacro } from './data/codeTypes';
 
 export default function updateMacros() {
     store.get('macros', []).then((data) => {
         invoke('update_macros', { macros: data
             .map(object => {
                 return ensureKeys(recursivelyReplaceKeys(
-                    object,
+                    normalizeMacro(object),
                     [
                         { from: 'macro', to: 'macro_' },
                         { from: 'type', to: 'type_' },
@@ -15,13 +17,80 @@ export default function updateMacros() {
                         { from: 'codeInside', to: 'code_inside' },
                         { from: 'activateTime', to: 'activate_time' },
                         { from: 'appPath', to: 'app_path' },
+                        { from: 'appEvent', to: 'app_event' },
+                        { from: 'intervalUnit', to: 'interval_unit' },
+                        { from: 'localTime', to: 'local_time' },
+                        { from: 'runMissed', to: 'run_missed' },
+                        { from: 'watchPath', to: 'watch_path' },
+                        { from: 'idleMinutes', to: 'idle_minutes' },
+                        { from: 'idleEvent', to: 'idle_event' },
+                        { from: 'sessionEvent', to: 'session_event' },
+                        { from: 'completedEvent', to: 'completed_event' },
                     ]
-                ), ["macro_", "name", "description"]);
+                ), ["macro_", "name", "description"], ["enabled"]);
             }).filter(object => object !== null)
+        }).then((problems) => {
+            problems.forEach(problem => console.warn(problem));
         });
     });
 }
 
+export function updatePaused() {
+    store.get('paused', false).then((paused) => {
+        invoke('pause_macros', { paused });
+    });
+}
+
+export function setPaused(paused) {
+    invoke('pause_macros', { paused });
+}
+
+// Lets go of any keys or mouse buttons that macros left held down
+export function releaseAllInputs() {
+    invoke('release_inputs');
+}
+
+export function setMacroEnabled(macroName, enabled) {
+    invoke('set_macro_enabled', { macroName, enabled });
+}
+
+// Macros can be paused and enabled or disabled from the tray too, so save the changes the backend makes
+export function listenForStateChanges() {
+    listen('paused-changed', (event) => {
+        store.set('paused', event.payload);
+    });
+    listen('enabled-changed', async (event) => {
+        const { macroName, initiator, enabled } = event.payload;
+        let macros = await store.get('macros', []);
+        let macro = macros.find(macro => macro.name === macroName);
+        if(!macro) return;
+        if(initiator === null) {
+            macro.enabled = enabled;
+        } else if(macro.macro?.initiators?.[initiator]) {
+            macro.macro.initiators[initiator].enabled = enabled;
+        }
+        store.set('macros', macros);
+    });
+}
+
+// Runs a macro by hand. A dry run returns what the macro would do instead of doing it.
+export function runMacro(macroName, initiator, dryRun = false) {
+    return invoke('run_macro', { macroName, initiator, variables: null, dryRun });
+}
+
+export function updateSettings() {
+    store.get('settings', {}).then((settings) => {
+        invoke('update_settings', { settings });
+    });
+}
+
+export async function setSetting(key, value) {
+    let settings = await store.get('settings', {});
+    settings[key] = value;
+    store.set('settings', settings);
+    invoke('update_settings', { settings });
+}
+
 function recursivelyReplaceKeys(object, replacements) {
     if(object instanceof Array) {
         return object.map(item => recursivelyReplaceKeys(item, replacements));
@@ -41,7 +110,7 @@ function recursivelyReplaceKeys(object, replacements) {
     return object;
 }
 
-function ensureKeys(object, keys) {
+function ensureKeys(object, keys, optionalKeys = []) {
     // Remove all keys that are not in the keys array and return null if there are keys not in the keys array.
     let result = {};
     for(const key of keys) {
@@ -51,6 +120,11 @@ function ensureKeys(object, keys) {
             return null;
         }
     }
+    for(const key of optionalKeys) {
+        if(object.hasOwnProperty(key)) {
+            result[key] = object[key];
+        }
+    }
     return result;
 }
 